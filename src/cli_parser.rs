@@ -1,10 +1,25 @@
 use anyhow::{Context, Result};
-use clap::Parser;
-use reqwest::blocking::get;
-use serde::Deserialize;
-use std::collections::HashMap;
+use base64::Engine;
+use clap::{Parser, Subcommand};
+use ed25519_dalek::{Signature, VerifyingKey};
+use schemars::JsonSchema;
+use serde::de::{self, MapAccess, Visitor};
+use serde::{Deserialize, Deserializer};
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
 use std::fs;
+use std::future::Future;
 use std::path::Path;
+use std::pin::Pin;
+
+use crate::conditions::ToolCondition;
+use crate::filters::OutputFilter;
+use crate::http_client::{HttpAuth, HttpClient, HttpConfig, HttpSource, TlsConfig};
+use crate::interpolate::interpolate_yaml;
+use crate::output_files::{validate_patterns, MAX_OUTPUT_TTL_SECS};
+use crate::output_format::OutputFormat;
+use crate::scratch::ScratchConfig;
 
 #[derive(Parser)]
 #[command(name = "mycommandmcp")]
@@ -12,15 +27,39 @@ use std::path::Path;
 #[command(version = env!("CARGO_PKG_VERSION"))]
 pub struct Args {
     /// Path to the YAML configuration file
-    #[arg(short, long)]
+    #[arg(short, long, global = true)]
     pub config: Option<String>,
 
+    /// Name of the profile selecting which tools, prompts and resources are enabled
+    #[arg(short, long, global = true, env = "MYCOMMANDMCP_PROFILE")]
+    pub profile: Option<String>,
+
     /// Path to the log file (if specified, logs will be written to both file and terminal)
     #[arg(short, long)]
     pub log_file: Option<String>,
+
+    #[command(subcommand)]
+    pub command: Option<Commands>,
 }
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Subcommand)]
+pub enum Commands {
+    /// Check the configuration for problems without starting the server
+    Validate {
+        /// Print the findings as JSON
+        #[arg(long)]
+        json: bool,
+
+        /// Also exit with a non-zero status when there are warnings
+        #[arg(long)]
+        strict: bool,
+    },
+    /// Print the JSON Schema of the configuration file format
+    Schema,
+}
+
+#[derive(Debug, Deserialize, Clone, JsonSchema)]
+#[serde(deny_unknown_fields)]
 pub struct PromptConfig {
     pub name: String,
     pub description: String,
@@ -30,11 +69,191 @@ pub struct PromptConfig {
     pub path: Option<String>,
     #[serde(default)]
     pub url: Option<String>,
+    #[serde(default)]
+    pub messages: Vec<PromptMessageConfig>,
+    #[serde(default)]
+    pub command: Option<PromptCommandConfig>,
+    /// Template wrapping the command output, which is inserted at `{{output}}`
+    #[serde(default)]
+    pub template: Option<String>,
+    #[serde(default)]
+    pub arguments: Vec<PromptArgumentConfig>,
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    #[serde(default)]
+    pub auth: Option<HttpAuth>,
+    #[serde(default)]
+    pub tls: Option<TlsConfig>,
+    #[serde(skip)]
+    pub origin: DefinitionOrigin,
+}
+
+/// An argument accepted by a prompt, substituted at `{{name}}` placeholders
+#[derive(Debug, Deserialize, Clone, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct PromptArgumentConfig {
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub required: bool,
+}
+
+/// A configured tool whose stdout is used as the prompt text
+#[derive(Debug, Deserialize, Clone, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct PromptCommandConfig {
+    pub tool: String,
+    /// Arguments passed to the tool; when omitted the prompt argument values
+    /// are passed in declaration order
+    #[serde(default)]
+    pub args: Option<String>,
+    #[serde(default)]
+    pub input: Option<String>,
+}
+
+/// Replace `{{key}}` placeholders in a template with the given values in a
+/// single left-to-right pass. Inserted values are never scanned again, and
+/// unknown placeholders are left as they are
+pub fn render_template(template: &str, values: &HashMap<String, String>) -> String {
+    let mut rendered = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        rendered.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        match after
+            .find("}}")
+            .and_then(|end| Some((end, values.get(&after[..end])?)))
+        {
+            Some((end, value)) => {
+                rendered.push_str(value);
+                rest = &after[end + 2..];
+            }
+            None => {
+                rendered.push_str("{{");
+                rest = after;
+            }
+        }
+    }
+    rendered.push_str(rest);
+    rendered
+}
+
+/// Role of a message in a multi-message prompt
+#[derive(Debug, Deserialize, Clone, Copy, Default, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum PromptRole {
+    #[default]
+    User,
+    Assistant,
+}
+
+impl PromptRole {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PromptRole::User => "user",
+            PromptRole::Assistant => "assistant",
+        }
+    }
+}
+
+/// A single message of a multi-message prompt
+#[derive(Debug, Deserialize, Clone, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct PromptMessageConfig {
+    #[serde(default)]
+    pub role: PromptRole,
+    #[serde(default)]
+    pub content: Option<String>,
+    #[serde(default)]
+    pub path: Option<String>,
+    /// Name of a configured resource embedded as MCP `resource` content
+    #[serde(default)]
+    pub resource: Option<String>,
+}
+
+impl PromptMessageConfig {
+    /// Load the text of this message from inline content or file.
+    /// Returns `None` for messages that embed a resource.
+    pub fn load_text(&self) -> Result<Option<String>> {
+        if let Some(content) = &self.content {
+            return Ok(Some(content.clone()));
+        }
+
+        if let Some(path) = &self.path {
+            return fs::read_to_string(path)
+                .map(Some)
+                .context(format!("Failed to read prompt message file: {}", path));
+        }
+
+        Ok(None)
+    }
 }
 
 impl PromptConfig {
-    /// Load the content for this prompt, either from inline content, file, or URL
-    pub fn load_content(&self) -> Result<String> {
+    /// Resolve the prompt argument values, failing when a required one is missing
+    pub fn resolve_arguments(
+        &self,
+        provided: &HashMap<String, String>,
+    ) -> Result<HashMap<String, String>> {
+        let mut values = HashMap::new();
+        for argument in &self.arguments {
+            match provided.get(&argument.name) {
+                Some(value) => {
+                    values.insert(argument.name.clone(), value.clone());
+                }
+                None if argument.required => {
+                    return Err(anyhow::anyhow!(
+                        "Missing required argument '{}' for prompt: {}",
+                        argument.name,
+                        self.name
+                    ));
+                }
+                None => {
+                    values.insert(argument.name.clone(), String::new());
+                }
+            }
+        }
+        Ok(values)
+    }
+
+    /// Build the tool arguments for a command prompt
+    pub fn command_args(&self, values: &HashMap<String, String>) -> Option<String> {
+        let command = self.command.as_ref()?;
+        match &command.args {
+            Some(args) => Some(render_template(args, values)),
+            None => {
+                let args = self
+                    .arguments
+                    .iter()
+                    .filter_map(|argument| values.get(&argument.name))
+                    .filter(|value| !value.is_empty())
+                    .cloned()
+                    .collect::<Vec<_>>();
+                if args.is_empty() {
+                    None
+                } else {
+                    Some(args.join(" "))
+                }
+            }
+        }
+    }
+
+    /// Build the prompt text from the output of its command, applying the template if any
+    pub fn render_command_output(&self, output: &str, values: &HashMap<String, String>) -> String {
+        match &self.template {
+            Some(template) => {
+                let mut values = values.clone();
+                values.insert("output".to_string(), output.to_string());
+                render_template(template, &values)
+            }
+            None => output.to_string(),
+        }
+    }
+
+    /// Load the content for this prompt, either from inline content, file, or URL.
+    /// Command prompts are executed by the server and are not handled here.
+    pub async fn load_content(&self, http: &HttpClient) -> Result<String> {
         if let Some(content) = &self.content {
             return Ok(content.clone());
         }
@@ -45,7 +264,7 @@ impl PromptConfig {
         }
 
         if let Some(url) = &self.url {
-            return Self::load_from_url(url);
+            return Self::load_from_url(http, url, &self.http_source()).await;
         }
 
         Err(anyhow::anyhow!(
@@ -54,38 +273,74 @@ impl PromptConfig {
         ))
     }
 
-    /// Load content from a URL
-    fn load_from_url(url: &str) -> Result<String> {
-        let response = get(url).context(format!("Failed to fetch URL: {}", url))?;
-
-        if !response.status().is_success() {
-            return Err(anyhow::anyhow!(
-                "HTTP error {} when fetching URL: {}",
-                response.status(),
-                url
-            ));
+    /// Request options used when loading the prompt from its URL
+    pub fn http_source(&self) -> HttpSource<'_> {
+        HttpSource {
+            headers: Some(&self.headers),
+            auth: self.auth.as_ref(),
+            tls: self.tls.as_ref(),
         }
+    }
 
-        let content = response
-            .text()
-            .context(format!("Failed to read response body from URL: {}", url))?;
-
-        Ok(content)
+    /// Load content from a URL
+    async fn load_from_url(
+        http: &HttpClient,
+        url: &str,
+        source: &HttpSource<'_>,
+    ) -> Result<String> {
+        http.fetch_text(url, source).await
     }
 }
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, Clone, JsonSchema)]
+#[serde(deny_unknown_fields)]
 pub struct ResourceConfig {
     pub name: String,
     pub description: String,
     pub path: String,
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    #[serde(default)]
+    pub auth: Option<HttpAuth>,
+    #[serde(default)]
+    pub tls: Option<TlsConfig>,
+    #[serde(skip)]
+    pub origin: DefinitionOrigin,
 }
 
-#[derive(Debug, Deserialize, Clone)]
+impl ResourceConfig {
+    /// Request options used when the resource path is a URL
+    pub fn http_source(&self) -> HttpSource<'_> {
+        HttpSource {
+            headers: Some(&self.headers),
+            auth: self.auth.as_ref(),
+            tls: self.tls.as_ref(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone, JsonSchema)]
+#[serde(deny_unknown_fields)]
 pub struct ToolConfig {
     pub name: String,
     pub description: String,
+    #[serde(default)]
     pub command: String,
+    /// Inline script body, run with `interpreter` instead of `command`
+    #[serde(default)]
+    pub script: Option<String>,
+    /// Interpreter that runs `script`, defaults to `sh`
+    #[serde(default)]
+    pub interpreter: Option<String>,
+    /// Commands run in sequence, each reading the output of the previous one
+    #[serde(default)]
+    pub pipeline: Vec<PipelineStep>,
+    /// Which step's exit status a pipeline reports
+    #[serde(default)]
+    pub on_failure: FailurePolicy,
+    /// Named steps run in order, each calling a tool or a command
+    #[serde(default)]
+    pub workflow: Vec<WorkflowStep>,
     pub path: String,
     pub accepts_args: bool,
     pub accept_input: bool,
@@ -95,9 +350,170 @@ pub struct ToolConfig {
     pub content_type: Option<String>,
     #[serde(default)]
     pub content_disposition: Option<String>,
+    /// Conditions under which the tool is available, it is hidden otherwise
+    #[serde(default)]
+    pub when: Option<ToolCondition>,
+    /// Exit codes that mean the command succeeded
+    #[serde(default = "default_success_codes")]
+    pub success_codes: Vec<i32>,
+    /// Human-readable meaning of exit codes, included in the result
+    #[serde(default, deserialize_with = "deserialize_exit_codes")]
+    #[schemars(with = "HashMap<String, String>")]
+    pub exit_codes: HashMap<i32, String>,
+    /// Report the call as failed when the command writes to stderr
+    #[serde(default)]
+    pub stderr_is_error: bool,
+    /// Run every call in a fresh temporary working directory instead of `path`
+    #[serde(default)]
+    pub scratch: Option<ScratchConfig>,
+    /// Glob patterns of files written by the command, returned as temporary resources.
+    /// The command runs in a fresh temp directory when set
+    #[serde(default)]
+    pub output_files: Vec<String>,
+    /// How long output files stay available as resources
+    #[serde(default = "default_output_ttl_secs")]
+    pub output_ttl_secs: u64,
+    /// Truncate stdout to this many bytes
+    #[serde(default)]
+    pub max_output_bytes: Option<usize>,
+    /// How the result is returned in `tools/call`
+    #[serde(default)]
+    pub presentation: Presentation,
+    /// Post-processing applied to stdout, in order
+    #[serde(default)]
+    pub filters: Vec<OutputFilter>,
+    /// Parse stdout into structured content
+    #[serde(default)]
+    pub output_format: Option<OutputFormat>,
+    /// JSON Schema of the structured content, replacing the one derived from `output_format`
+    #[serde(default)]
+    pub output_schema: Option<serde_json::Value>,
+    #[serde(skip)]
+    pub origin: DefinitionOrigin,
+}
+
+/// How the result of a tool is returned in `tools/call`
+#[derive(Debug, Deserialize, Clone, Copy, Default, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Presentation {
+    /// The whole result serialized as JSON in one text block
+    #[default]
+    Json,
+    /// Separate text blocks for stdout, stderr and the exit status
+    Blocks,
+}
+
+fn default_output_ttl_secs() -> u64 {
+    3600
+}
+
+fn default_success_codes() -> Vec<i32> {
+    vec![0]
+}
+
+/// A command of a pipeline tool
+#[derive(Debug, Deserialize, Clone, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct PipelineStep {
+    pub command: String,
+    /// Fixed arguments of the step
+    #[serde(default)]
+    pub args: Option<String>,
+    /// Append the arguments of the tool call to this step
+    #[serde(default)]
+    pub accepts_args: bool,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, Default, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum FailurePolicy {
+    /// Report the exit code of the last failing step
+    #[default]
+    Pipefail,
+    /// Report the exit code of the last step
+    Continue,
+}
+
+/// A named step of a workflow tool. `args`, `input` and `if` are templates
+/// that can refer to `{{args}}`, `{{input}}` and the results of earlier steps
+/// as `{{steps.<name>.output}}`, `{{steps.<name>.status_code}}`,
+/// `{{steps.<name>.error}}` and `{{steps.<name>.json.<path>}}`
+#[derive(Debug, Deserialize, Clone, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct WorkflowStep {
+    pub name: String,
+    /// Name of a configured tool to call
+    #[serde(default)]
+    pub tool: Option<String>,
+    /// Command to execute in the workflow tool's path, or its scratch directory
+    #[serde(default)]
+    pub command: Option<String>,
+    #[serde(default)]
+    pub args: Option<String>,
+    #[serde(default)]
+    pub input: Option<String>,
+    /// Condition deciding whether the step runs, e.g. `{{steps.build.status_code}} == 0`
+    #[serde(default, rename = "if")]
+    pub condition: Option<String>,
+    /// Keep running later steps when this one exits with a non-zero status
+    #[serde(default)]
+    pub continue_on_error: bool,
+}
+
+/// Evaluate a workflow condition: `a == b`, `a != b`, or a single value that
+/// is true unless it is empty, `false` or `0`. The operator is found in the
+/// template before rendering, so values containing `==` or `!=` are compared
+/// as they are
+pub fn evaluate_condition(condition: &str, values: &HashMap<String, String>) -> bool {
+    let render = |side: &str| render_template(side, values).trim().to_string();
+    if let Some((left, right)) = condition.split_once("!=") {
+        return render(left) != render(right);
+    }
+    if let Some((left, right)) = condition.split_once("==") {
+        return render(left) == render(right);
+    }
+    !matches!(render(condition).as_str(), "" | "false" | "0")
 }
 
-#[derive(Debug, Deserialize)]
+impl ToolConfig {
+    /// JSON Schema of the structured content of the tool, if it has any
+    pub fn output_schema(&self) -> Option<serde_json::Value> {
+        self.output_schema
+            .clone()
+            .or_else(|| self.output_format.as_ref().map(|format| format.schema()))
+    }
+
+    /// The executable that is spawned: the interpreter for inline scripts,
+    /// the first step of pipelines, the command otherwise
+    pub fn program(&self) -> &str {
+        match (&self.script, self.pipeline.first()) {
+            (Some(_), _) => self.interpreter.as_deref().unwrap_or("sh"),
+            (None, Some(step)) => &step.command,
+            (None, None) => &self.command,
+        }
+    }
+
+    /// Every executable the tool spawns. Workflow steps calling other tools
+    /// are left out, those tools spawn their own
+    pub fn programs(&self) -> Vec<&str> {
+        if !self.workflow.is_empty() {
+            self.workflow
+                .iter()
+                .filter_map(|step| step.command.as_deref())
+                .collect()
+        } else if !self.pipeline.is_empty() {
+            self.pipeline
+                .iter()
+                .map(|step| step.command.as_str())
+                .collect()
+        } else {
+            vec![self.program()]
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
 pub struct ToolsConfig {
     #[serde(default)]
     pub tools: Vec<ToolConfig>,
@@ -105,14 +521,208 @@ pub struct ToolsConfig {
     pub prompts: Vec<PromptConfig>,
     #[serde(default)]
     pub resources: Vec<ResourceConfig>,
+    #[serde(default, deserialize_with = "deserialize_external_configs")]
+    #[schemars(with = "Vec<ExternalConfigRepr>")]
+    pub external_configs: Vec<ExternalConfigEntry>,
+    #[serde(default)]
+    pub http: HttpConfig,
+    #[serde(default)]
+    pub profiles: HashMap<String, ProfileConfig>,
+}
+
+/// Definitions enabled in a named profile. A list that is left out enables
+/// every definition of that kind.
+#[derive(Debug, Deserialize, Clone, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct ProfileConfig {
+    #[serde(default)]
+    pub tools: Option<Vec<String>>,
     #[serde(default)]
-    pub external_configs: Vec<String>,
+    pub prompts: Option<Vec<String>>,
+    #[serde(default)]
+    pub resources: Option<Vec<String>>,
+}
+
+/// An external configuration source, either a plain path/URL or a map with options
+#[derive(Debug, Deserialize, Clone, Default, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct ExternalConfigEntry {
+    pub source: String,
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    #[serde(default)]
+    pub auth: Option<HttpAuth>,
+    #[serde(default)]
+    pub tls: Option<TlsConfig>,
+    /// Expected SHA-256 digest of the file, as hex
+    #[serde(default)]
+    pub sha256: Option<String>,
+    #[serde(default)]
+    pub signature: Option<SignatureConfig>,
+    /// Prefix for the names of the tools, prompts and resources of this file
+    #[serde(default)]
+    pub namespace: Option<String>,
+    /// Let definitions from other files replace the ones from this file
+    #[serde(default, rename = "override")]
+    pub overridable: bool,
+}
+
+/// Detached Ed25519 signature verification for an external config
+#[derive(Debug, Deserialize, Clone, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct SignatureConfig {
+    /// Base64-encoded Ed25519 public key
+    pub public_key: String,
+    /// Path or URL of the base64-encoded signature, defaults to the source with `.sig` appended
+    #[serde(default)]
+    pub source: Option<String>,
+}
+
+#[derive(JsonSchema)]
+#[serde(untagged)]
+enum ExternalConfigRepr {
+    Source(String),
+    Detailed(Box<ExternalConfigEntry>),
+}
+
+// Deserialized by hand rather than as an untagged enum so that errors inside a
+// detailed entry, such as unknown fields, are reported instead of a generic
+// "did not match any variant" message
+impl<'de> Deserialize<'de> for ExternalConfigRepr {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct ReprVisitor;
+
+        impl<'de> Visitor<'de> for ReprVisitor {
+            type Value = ExternalConfigRepr;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a path or URL, or a map with a `source` key")
+            }
+
+            fn visit_str<E: de::Error>(self, value: &str) -> std::result::Result<Self::Value, E> {
+                Ok(ExternalConfigRepr::Source(value.to_string()))
+            }
+
+            fn visit_map<A: MapAccess<'de>>(
+                self,
+                map: A,
+            ) -> std::result::Result<Self::Value, A::Error> {
+                ExternalConfigEntry::deserialize(de::value::MapAccessDeserializer::new(map))
+                    .map(|entry| ExternalConfigRepr::Detailed(Box::new(entry)))
+            }
+        }
+
+        deserializer.deserialize_any(ReprVisitor)
+    }
+}
+
+/// An exit code used as a map key, written as an integer in YAML and as a
+/// string in JSON and TOML, whose keys are always strings
+#[derive(PartialEq, Eq, Hash)]
+struct ExitCodeKey(i32);
+
+impl<'de> Deserialize<'de> for ExitCodeKey {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct KeyVisitor;
+
+        impl Visitor<'_> for KeyVisitor {
+            type Value = ExitCodeKey;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("an exit code")
+            }
+
+            fn visit_i64<E: de::Error>(self, value: i64) -> std::result::Result<ExitCodeKey, E> {
+                i32::try_from(value)
+                    .map(ExitCodeKey)
+                    .map_err(|_| E::custom(format!("exit code out of range: {value}")))
+            }
+
+            fn visit_u64<E: de::Error>(self, value: u64) -> std::result::Result<ExitCodeKey, E> {
+                i32::try_from(value)
+                    .map(ExitCodeKey)
+                    .map_err(|_| E::custom(format!("exit code out of range: {value}")))
+            }
+
+            fn visit_str<E: de::Error>(self, value: &str) -> std::result::Result<ExitCodeKey, E> {
+                value
+                    .trim()
+                    .parse()
+                    .map(ExitCodeKey)
+                    .map_err(|_| E::custom(format!("invalid exit code: {value}")))
+            }
+        }
+
+        deserializer.deserialize_any(KeyVisitor)
+    }
+}
+
+fn deserialize_exit_codes<'de, D>(
+    deserializer: D,
+) -> std::result::Result<HashMap<i32, String>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let codes = HashMap::<ExitCodeKey, String>::deserialize(deserializer)?;
+    Ok(codes
+        .into_iter()
+        .map(|(code, meaning)| (code.0, meaning))
+        .collect())
+}
+
+fn deserialize_external_configs<'de, D>(
+    deserializer: D,
+) -> std::result::Result<Vec<ExternalConfigEntry>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    // An `external_configs:` key with every entry commented out is null
+    let entries = Option::<Vec<ExternalConfigRepr>>::deserialize(deserializer)?;
+    Ok(entries
+        .unwrap_or_default()
+        .into_iter()
+        .map(|entry| match entry {
+            ExternalConfigRepr::Source(source) => ExternalConfigEntry {
+                source,
+                ..Default::default()
+            },
+            ExternalConfigRepr::Detailed(entry) => *entry,
+        })
+        .collect())
+}
+
+impl ExternalConfigEntry {
+    /// Request options used when the source is a URL
+    pub fn http_source(&self) -> HttpSource<'_> {
+        HttpSource {
+            headers: Some(&self.headers),
+            auth: self.auth.as_ref(),
+            tls: self.tls.as_ref(),
+        }
+    }
 }
 
 pub struct ConfigData {
     pub tools: HashMap<String, ToolConfig>,
     pub prompts: HashMap<String, PromptConfig>,
     pub resources: HashMap<String, ResourceConfig>,
+    pub http: HttpClient,
+    /// Non-fatal notes about the merged configuration, such as overridden definitions
+    pub warnings: Vec<String>,
+    /// Definitions hidden because their `when` conditions do not hold, or
+    /// because they depend on a definition the profile disables, with the reason
+    pub skipped: Vec<String>,
+}
+
+/// Check whether a source refers to a remote URL rather than a local file
+pub fn is_url(source: &str) -> bool {
+    source.starts_with("http://") || source.starts_with("https://")
 }
 
 /// Find the configuration file in the appropriate location based on the OS
@@ -156,112 +766,1432 @@ pub fn find_config_file(explicit_path: Option<String>) -> Result<String> {
     Ok(CONFIG_FILENAME.to_string())
 }
 
-/// Load and parse an external configuration file from path or URL
-fn load_external_config(source: &str) -> Result<ToolsConfig> {
-    let content = if source.starts_with("http://") || source.starts_with("https://") {
-        // Load from URL
-        let response = get(source).context(format!("Failed to fetch URL: {}", source))?;
-        if !response.status().is_success() {
+/// Add a "did you mean" hint to unknown field and variant errors
+fn with_suggestion(message: String) -> anyhow::Error {
+    let unknown = ["unknown field `", "unknown variant `"]
+        .iter()
+        .find_map(|marker| message.split_once(marker))
+        .and_then(|(_, rest)| rest.split_once('`'));
+
+    let suggestion = unknown.and_then(|(name, rest)| {
+        rest.split('`')
+            .skip(1)
+            .step_by(2)
+            .map(|candidate| (strsim::levenshtein(name, candidate), candidate))
+            .filter(|(distance, candidate)| *distance <= (candidate.len() / 3).max(2))
+            .min_by_key(|(distance, _)| *distance)
+            .map(|(_, candidate)| candidate.to_string())
+    });
+
+    let message = message.trim_end();
+    match suggestion {
+        Some(suggestion) => anyhow::anyhow!("{message} (did you mean `{suggestion}`?)"),
+        None => anyhow::anyhow!(message.to_string()),
+    }
+}
+
+/// Build the JSON Schema of the configuration file format
+pub fn config_schema() -> serde_json::Value {
+    serde_json::to_value(schemars::schema_for!(ToolsConfig)).unwrap_or_default()
+}
+
+/// Syntax of a configuration file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigFormat {
+    Yaml,
+    Json,
+    Toml,
+}
+
+impl ConfigFormat {
+    /// Detect the format from the Content-Type of a URL response, falling back
+    /// to the file extension and then to YAML
+    pub fn detect(source: &str, content_type: Option<&str>) -> Self {
+        let mime = content_type
+            .and_then(|ct| ct.split(';').next())
+            .map(|ct| ct.trim().to_ascii_lowercase());
+        match mime.as_deref() {
+            Some("application/json") => return ConfigFormat::Json,
+            Some("application/toml") | Some("text/x-toml") => return ConfigFormat::Toml,
+            Some("application/yaml")
+            | Some("application/x-yaml")
+            | Some("text/yaml")
+            | Some("text/x-yaml") => return ConfigFormat::Yaml,
+            _ => {}
+        }
+
+        // Ignore the query string and fragment of URLs
+        let path = source.split(['?', '#']).next().unwrap_or(source);
+        match Path::new(path)
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(|ext| ext.to_ascii_lowercase())
+            .as_deref()
+        {
+            Some("json") => ConfigFormat::Json,
+            Some("toml") => ConfigFormat::Toml,
+            _ => ConfigFormat::Yaml,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            ConfigFormat::Yaml => "YAML",
+            ConfigFormat::Json => "JSON",
+            ConfigFormat::Toml => "TOML",
+        }
+    }
+
+    /// Deserialize a document in this format
+    fn parse<T: serde::de::DeserializeOwned>(&self, content: &str) -> Result<T> {
+        match self {
+            ConfigFormat::Yaml => {
+                serde_yaml::from_str(content).map_err(|e| with_suggestion(e.to_string()))
+            }
+            ConfigFormat::Json => {
+                serde_json::from_str(content).map_err(|e| with_suggestion(e.to_string()))
+            }
+            ConfigFormat::Toml => {
+                toml::from_str(content).map_err(|e| with_suggestion(e.to_string()))
+            }
+        }
+    }
+}
+
+/// Parse a configuration document, expanding `${VAR}`, `${VAR:-default}` and
+/// `~` in every string value when `interpolate` is set
+fn parse_config(
+    content: &str,
+    source: &str,
+    format: ConfigFormat,
+    interpolate: bool,
+) -> Result<ToolsConfig> {
+    let error_context = || format!("Failed to parse {} from {}", format.name(), source);
+
+    // Deserialize the raw document first so that structural errors report
+    // their line and column, which are lost once it is converted to a Value
+    format
+        .parse::<ToolsConfig>(content)
+        .with_context(error_context)?;
+
+    let mut value: serde_yaml::Value = format.parse(content).with_context(error_context)?;
+    if interpolate {
+        interpolate_yaml(&mut value, "").context(format!("Invalid configuration in {}", source))?;
+    }
+
+    serde_yaml::from_value(value).with_context(error_context)
+}
+
+/// Read the raw bytes of a local file or URL source
+async fn read_source(
+    http: &HttpClient,
+    source: &str,
+    options: &HttpSource<'_>,
+) -> Result<(Vec<u8>, Option<String>)> {
+    if is_url(source) {
+        let response = http.fetch(source, options).await?;
+        Ok((response.body, response.content_type))
+    } else {
+        let content =
+            fs::read(source).context(format!("Failed to read config file: {}", source))?;
+        Ok((content, None))
+    }
+}
+
+/// Check the integrity pin and signature of an external config
+async fn verify_external_config(
+    http: &HttpClient,
+    entry: &ExternalConfigEntry,
+    content: &[u8],
+) -> Result<()> {
+    if let Some(expected) = &entry.sha256 {
+        let actual = format!("{:x}", Sha256::digest(content));
+        if !actual.eq_ignore_ascii_case(expected.trim()) {
             return Err(anyhow::anyhow!(
-                "HTTP error {} when fetching URL: {}",
-                response.status(),
-                source
+                "SHA-256 mismatch for external config {}: expected {}, got {}",
+                entry.source,
+                expected.trim(),
+                actual
             ));
         }
-        response
-            .text()
-            .context(format!("Failed to read response body from URL: {}", source))?
-    } else {
-        // Load from file
-        fs::read_to_string(source).context(format!("Failed to read config file: {}", source))?
+    }
+
+    if let Some(signature) = &entry.signature {
+        let engine = base64::engine::general_purpose::STANDARD;
+        let public_key: [u8; 32] = engine
+            .decode(signature.public_key.trim())
+            .ok()
+            .and_then(|key| key.try_into().ok())
+            .context(format!(
+                "Invalid Ed25519 public key for external config: {}",
+                entry.source
+            ))?;
+        let public_key = VerifyingKey::from_bytes(&public_key).context(format!(
+            "Invalid Ed25519 public key for external config: {}",
+            entry.source
+        ))?;
+
+        // A relative signature source is resolved like an include of the config
+        let signature_source = match &signature.source {
+            Some(source) => resolve_source(&entry.source, source)?,
+            None => format!("{}.sig", entry.source),
+        };
+        let (encoded, _) = read_source(http, &signature_source, &entry.http_source()).await?;
+        let decoded: [u8; 64] = engine
+            .decode(String::from_utf8_lossy(&encoded).trim())
+            .ok()
+            .and_then(|sig| sig.try_into().ok())
+            .context(format!("Invalid Ed25519 signature in {}", signature_source))?;
+
+        public_key
+            .verify_strict(content, &Signature::from_bytes(&decoded))
+            .map_err(|_| {
+                anyhow::anyhow!(
+                    "Signature verification failed for external config: {}",
+                    entry.source
+                )
+            })?;
+    }
+
+    Ok(())
+}
+
+/// Load and parse an external configuration file from path or URL, returning
+/// its raw content along with it
+async fn load_external_config(
+    http: &HttpClient,
+    entry: &ExternalConfigEntry,
+) -> Result<(ToolsConfig, Vec<u8>)> {
+    let source = entry.source.as_str();
+    let (content, content_type) = read_source(http, source, &entry.http_source()).await?;
+
+    verify_external_config(http, entry, &content).await?;
+
+    let text = String::from_utf8(content.clone())
+        .context(format!("External config is not valid UTF-8: {}", source))?;
+
+    // Remote files are not expanded, so they cannot read local environment
+    // variables and send them out in the URLs of their own includes
+    let format = ConfigFormat::detect(source, content_type.as_deref());
+    let config = parse_config(&text, source, format, !is_url(source))?;
+    Ok((config, content))
+}
+
+/// Where a tool, prompt or resource definition was loaded from
+#[derive(Debug, Clone, Default)]
+pub struct DefinitionOrigin {
+    pub source: String,
+    /// Loaded through an `override: true` include, so other files may replace it
+    pub overridable: bool,
+}
+
+/// A named definition merged from the configuration files
+pub trait Definition {
+    fn name(&self) -> &str;
+    fn origin(&self) -> &DefinitionOrigin;
+}
+
+impl Definition for ToolConfig {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn origin(&self) -> &DefinitionOrigin {
+        &self.origin
+    }
+}
+
+impl Definition for PromptConfig {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn origin(&self) -> &DefinitionOrigin {
+        &self.origin
+    }
+}
+
+impl Definition for ResourceConfig {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn origin(&self) -> &DefinitionOrigin {
+        &self.origin
+    }
+}
+
+/// Record the origin of the definitions of a config file and apply its namespace.
+/// References between definitions of the same file are renamed along with them.
+fn tag_definitions(config: &mut ToolsConfig, origin: &DefinitionOrigin, namespace: &str) {
+    let tool_names: HashSet<String> = config.tools.iter().map(|t| t.name.clone()).collect();
+    let resource_names: HashSet<String> = config.resources.iter().map(|r| r.name.clone()).collect();
+
+    for tool in &mut config.tools {
+        tool.name = format!("{namespace}{}", tool.name);
+        tool.origin = origin.clone();
+
+        for step in &mut tool.workflow {
+            if let Some(step_tool) = &mut step.tool {
+                if tool_names.contains(step_tool) {
+                    *step_tool = format!("{namespace}{step_tool}");
+                }
+            }
+        }
+    }
+
+    for resource in &mut config.resources {
+        resource.name = format!("{namespace}{}", resource.name);
+        resource.origin = origin.clone();
+    }
+
+    for prompt in &mut config.prompts {
+        prompt.name = format!("{namespace}{}", prompt.name);
+        prompt.origin = origin.clone();
+
+        if let Some(command) = &mut prompt.command {
+            if tool_names.contains(&command.tool) {
+                command.tool = format!("{namespace}{}", command.tool);
+            }
+        }
+
+        for message in &mut prompt.messages {
+            if let Some(resource) = &mut message.resource {
+                if resource_names.contains(resource) {
+                    *resource = format!("{namespace}{resource}");
+                }
+            }
+        }
+    }
+}
+
+/// Keep only the definitions of `kind` whose names are enabled by a profile list
+fn retain_enabled<T: Definition>(
+    definitions: &mut Vec<T>,
+    enabled: &Option<Vec<String>>,
+    kind: &str,
+) -> Result<()> {
+    let Some(enabled) = enabled else {
+        return Ok(());
     };
 
-    let config: ToolsConfig =
-        serde_yaml::from_str(&content).context(format!("Failed to parse YAML from {}", source))?;
+    if let Some(unknown) = enabled
+        .iter()
+        .find(|name| !definitions.iter().any(|def| def.name() == name.as_str()))
+    {
+        return Err(anyhow::anyhow!("Unknown {kind} in profile: {unknown}"));
+    }
 
-    Ok(config)
+    definitions.retain(|def| enabled.iter().any(|name| name == def.name()));
+    Ok(())
 }
 
-/// Load and parse the configuration file
-pub fn load_config(config_path: &str) -> Result<ConfigData> {
-    let config_content = fs::read_to_string(config_path)
-        .context(format!("Failed to read config file: {config_path}"))?;
+/// Drop the definitions that are not enabled in the selected profile, and the
+/// workflows and prompts left out of the profile that depend on a disabled
+/// tool or resource. Returns why each dependent definition was dropped.
+fn apply_profile(config: &mut ToolsConfig, profile: &str) -> Result<Vec<String>> {
+    let Some(selected) = config.profiles.get(profile) else {
+        let mut available: Vec<_> = config.profiles.keys().cloned().collect();
+        available.sort();
+        return Err(anyhow::anyhow!(
+            "Unknown profile (available: {})",
+            if available.is_empty() {
+                "none".to_string()
+            } else {
+                available.join(", ")
+            }
+        ));
+    };
 
-    let mut config: ToolsConfig =
-        serde_yaml::from_str(&config_content).context("Failed to parse YAML configuration")?;
+    let selected = selected.clone();
+    let tool_names: HashSet<String> = config.tools.iter().map(|t| t.name.clone()).collect();
+    let resource_names: HashSet<String> = config.resources.iter().map(|r| r.name.clone()).collect();
 
-    // Keep track of loaded external files to avoid duplicates
-    let mut loaded_files = std::collections::HashSet::new();
+    retain_enabled(&mut config.tools, &selected.tools, "tool")?;
+    retain_enabled(&mut config.prompts, &selected.prompts, "prompt")?;
+    retain_enabled(&mut config.resources, &selected.resources, "resource")?;
 
-    // Load external configurations and merge them
-    for source in &config.external_configs {
-        if !loaded_files.contains(source) {
-            let external = load_external_config(source)?;
-            config.tools.extend(external.tools);
-            config.prompts.extend(external.prompts);
-            config.resources.extend(external.resources);
-            loaded_files.insert(source.clone());
+    let disabled_tools: HashSet<String> = tool_names
+        .into_iter()
+        .filter(|name| !config.tools.iter().any(|tool| &tool.name == name))
+        .collect();
+    let disabled_resources: HashSet<String> = resource_names
+        .into_iter()
+        .filter(|name| !config.resources.iter().any(|res| &res.name == name))
+        .collect();
+
+    // Definitions the profile lists by name must work as listed
+    let listed = |names: &Option<Vec<String>>, name: &str| {
+        names
+            .as_ref()
+            .is_some_and(|names| names.iter().any(|n| n == name))
+    };
+    for tool in &config.tools {
+        if let Some(step_tool) = workflow_uses(tool, &disabled_tools) {
+            if listed(&selected.tools, &tool.name) {
+                return Err(anyhow::anyhow!(
+                    "Workflow tool '{}' is enabled but calls tool '{}', which the profile disables",
+                    tool.name,
+                    step_tool
+                ));
+            }
+        }
+    }
+    for prompt in &config.prompts {
+        if let Some(dependency) = prompt_uses(prompt, &disabled_tools, &disabled_resources) {
+            if listed(&selected.prompts, &prompt.name) {
+                return Err(anyhow::anyhow!(
+                    "Prompt '{}' is enabled but uses {}, which the profile disables",
+                    prompt.name,
+                    dependency
+                ));
+            }
         }
     }
 
-    let mut tools = HashMap::new();
-    for tool in config.tools {
-        if tools.contains_key(&tool.name) {
-            return Err(anyhow::anyhow!("Duplicate tool name: {}", tool.name));
+    Ok(drop_dependents(
+        config,
+        disabled_tools,
+        &disabled_resources,
+        &format!("is not enabled in profile '{profile}'"),
+    ))
+}
+
+/// The first step tool of a workflow that is in `tools`
+fn workflow_uses<'a>(tool: &'a ToolConfig, tools: &HashSet<String>) -> Option<&'a str> {
+    tool.workflow
+        .iter()
+        .filter_map(|step| step.tool.as_deref())
+        .find(|step_tool| tools.contains(*step_tool))
+}
+
+/// The tool run by a command prompt, or a resource embedded in its messages,
+/// that is in `tools` or `resources`, e.g. `tool 'git_diff'`
+fn prompt_uses(
+    prompt: &PromptConfig,
+    tools: &HashSet<String>,
+    resources: &HashSet<String>,
+) -> Option<String> {
+    if let Some(command) = prompt.command.as_ref().filter(|c| tools.contains(&c.tool)) {
+        return Some(format!("tool '{}'", command.tool));
+    }
+    prompt
+        .messages
+        .iter()
+        .filter_map(|message| message.resource.as_ref())
+        .find(|resource| resources.contains(*resource))
+        .map(|resource| format!("resource '{resource}'"))
+}
+
+/// Drop the workflows calling one of the `tools`, then the prompts running one
+/// of those tools or workflows or embedding one of the `resources`. Returns
+/// why each definition was dropped, `reason` telling what happened to the
+/// tool or resource.
+fn drop_dependents(
+    config: &mut ToolsConfig,
+    mut tools: HashSet<String>,
+    resources: &HashSet<String>,
+    reason: &str,
+) -> Vec<String> {
+    let mut skipped = Vec::new();
+    let mut skipped_workflows = Vec::new();
+    config
+        .tools
+        .retain(|tool| match workflow_uses(tool, &tools) {
+            Some(step_tool) => {
+                skipped.push(format!(
+                    "tool '{}' defined in {}: tool '{}' {}",
+                    tool.name, tool.origin.source, step_tool, reason
+                ));
+                skipped_workflows.push(tool.name.clone());
+                false
+            }
+            None => true,
+        });
+    tools.extend(skipped_workflows);
+    config
+        .prompts
+        .retain(|prompt| match prompt_uses(prompt, &tools, resources) {
+            Some(dependency) => {
+                skipped.push(format!(
+                    "prompt '{}' defined in {}: {} {}",
+                    prompt.name, prompt.origin.source, dependency, reason
+                ));
+                false
+            }
+            None => true,
+        });
+    skipped
+}
+
+/// Drop the tools whose `when` conditions do not hold on this machine, and the
+/// command prompts that run them. Returns why each definition was skipped.
+fn apply_conditions(config: &mut ToolsConfig) -> Vec<String> {
+    let mut skipped = Vec::new();
+    let mut skipped_tools = HashSet::new();
+
+    config.tools.retain(|tool| {
+        let Some(when) = &tool.when else {
+            return true;
+        };
+        match when.check() {
+            Ok(()) => true,
+            Err(reason) => {
+                skipped.push(format!(
+                    "tool '{}' defined in {}: {}",
+                    tool.name, tool.origin.source, reason
+                ));
+                skipped_tools.insert(tool.name.clone());
+                false
+            }
+        }
+    });
+
+    // A tool may be defined once per platform, so only drop prompts and
+    // workflows whose tool has no remaining definition
+    skipped_tools.retain(|name| !config.tools.iter().any(|tool| &tool.name == name));
+    skipped.extend(drop_dependents(
+        config,
+        skipped_tools,
+        &HashSet::new(),
+        "is not available",
+    ));
+    skipped
+}
+
+/// Maximum nesting depth of `external_configs` includes
+const MAX_INCLUDE_DEPTH: usize = 16;
+
+/// Directory next to the main config whose files are loaded as fragments
+const CONFIG_DIRECTORY: &str = "mycommand-tools.d";
+
+/// List the configuration fragments in the `mycommand-tools.d` directory next
+/// to the main config, in lexical order. Hidden files and files without a
+/// YAML, JSON or TOML extension are skipped.
+fn config_directory_entries(config_path: &str) -> Result<Vec<ExternalConfigEntry>> {
+    let base = Path::new(config_path).parent().unwrap_or(Path::new(""));
+    let dir = base.join(CONFIG_DIRECTORY);
+    if !dir.is_dir() {
+        return Ok(Vec::new());
+    }
+
+    let mut names = Vec::new();
+    for dir_entry in fs::read_dir(&dir).context(format!(
+        "Failed to read config directory: {}",
+        dir.display()
+    ))? {
+        let path = dir_entry?.path();
+        let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+            continue;
+        };
+        let extension = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(|ext| ext.to_ascii_lowercase());
+        let is_config = matches!(
+            extension.as_deref(),
+            Some("yaml") | Some("yml") | Some("json") | Some("toml")
+        );
+        if !name.starts_with('.') && is_config && path.is_file() {
+            names.push(name.to_string());
         }
-        tools.insert(tool.name.clone(), tool);
     }
+    names.sort();
 
-    let mut prompts = HashMap::new();
-    for prompt in config.prompts {
-        // Validate unique prompt names
-        if prompts.contains_key(&prompt.name) {
-            return Err(anyhow::anyhow!("Duplicate prompt name: {}", prompt.name));
+    // Sources are relative to the main config, like its own external_configs
+    Ok(names
+        .into_iter()
+        .map(|name| ExternalConfigEntry {
+            source: format!("{CONFIG_DIRECTORY}/{name}"),
+            ..ExternalConfigEntry::default()
+        })
+        .collect())
+}
+
+/// Resolve an include source relative to the file or URL that includes it
+fn resolve_source(parent: &str, source: &str) -> Result<String> {
+    if is_url(source) || Path::new(source).is_absolute() {
+        return Ok(source.to_string());
+    }
+
+    if is_url(parent) {
+        let base = reqwest::Url::parse(parent).context(format!("Invalid URL: {}", parent))?;
+        let url = base.join(source).context(format!(
+            "Failed to resolve '{}' relative to {}",
+            source, parent
+        ))?;
+        return Ok(url.to_string());
+    }
+
+    let base = Path::new(parent).parent().unwrap_or(Path::new(""));
+    Ok(base.join(source).to_string_lossy().to_string())
+}
+
+/// Identity of a source used for duplicate and cycle detection
+fn source_key(source: &str) -> String {
+    if is_url(source) {
+        return source.to_string();
+    }
+    fs::canonicalize(source)
+        .map(|path| path.to_string_lossy().to_string())
+        .unwrap_or_else(|_| source.to_string())
+}
+
+/// The config file whose `external_configs` are being loaded
+struct IncludeParent<'a> {
+    source: &'a str,
+    origin: &'a DefinitionOrigin,
+    namespace: &'a str,
+}
+
+/// Load the external configs included by `parent` and everything they include,
+/// merging their definitions into `merged` in include order
+fn load_includes<'a>(
+    http: &'a HttpClient,
+    parent: IncludeParent<'a>,
+    entries: &'a [ExternalConfigEntry],
+    chain: &'a mut Vec<String>,
+    loaded_files: &'a mut HashMap<String, Vec<u8>>,
+    merged: &'a mut ToolsConfig,
+) -> Pin<Box<dyn Future<Output = Result<()>> + 'a>> {
+    Box::pin(async move {
+        for entry in entries {
+            let source = resolve_source(parent.source, &entry.source)?;
+            let key = source_key(&source);
+
+            if chain.contains(&key) {
+                let mut cycle = chain.clone();
+                cycle.push(key);
+                return Err(anyhow::anyhow!(
+                    "Cycle detected in external_configs: {}",
+                    cycle.join(" -> ")
+                ));
+            }
+
+            let namespace = match &entry.namespace {
+                Some(entry_namespace) => format!("{}{entry_namespace}.", parent.namespace),
+                None => parent.namespace.to_string(),
+            };
+
+            let resolved = ExternalConfigEntry {
+                source: source.clone(),
+                ..entry.clone()
+            };
+
+            // The same file may be included again under a different namespace.
+            // A repeated include is skipped, but its own pins still have to
+            // match the content that was loaded
+            let loaded_key = format!("{namespace}{key}");
+            if let Some(content) = loaded_files.get(&loaded_key) {
+                verify_external_config(http, &resolved, content)
+                    .await
+                    .context(format!(
+                        "Failed to load external config {} included from {}",
+                        source, parent.source
+                    ))?;
+                continue;
+            }
+
+            if chain.len() > MAX_INCLUDE_DEPTH {
+                return Err(anyhow::anyhow!(
+                    "external_configs nested deeper than {} levels: {}",
+                    MAX_INCLUDE_DEPTH,
+                    chain.join(" -> ")
+                ));
+            }
+
+            let (mut external, content) =
+                load_external_config(http, &resolved)
+                    .await
+                    .context(format!(
+                        "Failed to load external config {} included from {}",
+                        source, parent.source
+                    ))?;
+            loaded_files.insert(loaded_key, content);
+
+            let origin = DefinitionOrigin {
+                source: source.clone(),
+                overridable: parent.origin.overridable || entry.overridable,
+            };
+            if !external.profiles.is_empty() {
+                return Err(anyhow::anyhow!(
+                    "profiles can only be defined in the main configuration file, found in {}",
+                    source
+                ));
+            }
+
+            tag_definitions(&mut external, &origin, &namespace);
+
+            merged.tools.append(&mut external.tools);
+            merged.prompts.append(&mut external.prompts);
+            merged.resources.append(&mut external.resources);
+
+            chain.push(key);
+            load_includes(
+                http,
+                IncludeParent {
+                    source: &source,
+                    origin: &origin,
+                    namespace: &namespace,
+                },
+                &external.external_configs,
+                chain,
+                loaded_files,
+                merged,
+            )
+            .await?;
+            chain.pop();
         }
 
-        // Validate that at least one content source is provided
-        let has_content = prompt.content.is_some();
-        let has_path = prompt.path.is_some();
-        let has_url = prompt.url.is_some();
+        Ok(())
+    })
+}
 
-        if !has_content && !has_path && !has_url {
+/// Validate that a tool runs exactly one of a command, an inline script or a pipeline
+fn validate_tool(tool: &ToolConfig) -> Result<()> {
+    let kinds = [
+        !tool.command.is_empty(),
+        tool.script.is_some(),
+        !tool.pipeline.is_empty(),
+        !tool.workflow.is_empty(),
+    ]
+    .iter()
+    .filter(|present| **present)
+    .count();
+
+    if kinds == 0 {
+        return Err(anyhow::anyhow!(
+            "Tool '{}' must specify one of 'command', 'script', 'pipeline' or 'workflow'",
+            tool.name
+        ));
+    }
+    if kinds > 1 {
+        return Err(anyhow::anyhow!(
+            "Tool '{}' can only specify one of 'command', 'script', 'pipeline' and 'workflow'",
+            tool.name
+        ));
+    }
+    if tool.interpreter.is_some() && tool.script.is_none() {
+        return Err(anyhow::anyhow!(
+            "Tool '{}' can only use 'interpreter' together with 'script'",
+            tool.name
+        ));
+    }
+    if tool.default_args.is_some() && !tool.pipeline.is_empty() {
+        return Err(anyhow::anyhow!(
+            "Tool '{}' cannot use 'default_args' with 'pipeline', set 'args' on the steps instead",
+            tool.name
+        ));
+    }
+
+    if tool.success_codes.is_empty() {
+        return Err(anyhow::anyhow!(
+            "Tool '{}' must list at least one exit code in 'success_codes'",
+            tool.name
+        ));
+    }
+
+    if !tool.output_files.is_empty() {
+        if !tool.workflow.is_empty() {
             return Err(anyhow::anyhow!(
-                "Prompt '{}' must have either 'content', 'path', or 'url' specified",
-                prompt.name
+                "Tool '{}' cannot use 'output_files' with 'workflow', set it on the called tools instead",
+                tool.name
             ));
         }
+        validate_patterns(&tool.output_files)
+            .context(format!("Invalid output_files of tool '{}'", tool.name))?;
+        if tool.output_ttl_secs > MAX_OUTPUT_TTL_SECS {
+            return Err(anyhow::anyhow!(
+                "Tool '{}' sets 'output_ttl_secs' above the maximum of {} seconds",
+                tool.name,
+                MAX_OUTPUT_TTL_SECS
+            ));
+        }
+    }
 
-        if has_content && (has_path || has_url) {
+    for filter in &tool.filters {
+        filter.validate().context(format!(
+            "Invalid {} filter of tool '{}'",
+            filter.name(),
+            tool.name
+        ))?;
+    }
+
+    match &tool.output_format {
+        Some(format) => {
+            format
+                .validate()
+                .context(format!("Invalid output_format of tool '{}'", tool.name))?;
+            if tool.content_type.is_some() {
+                return Err(anyhow::anyhow!(
+                    "Tool '{}' cannot use 'output_format' together with 'content_type'",
+                    tool.name
+                ));
+            }
+        }
+        None if tool.output_schema.is_some() => {
             return Err(anyhow::anyhow!(
-                "Prompt '{}' cannot have both 'content' and 'path'/'url' specified",
+                "Tool '{}' can only use 'output_schema' together with 'output_format'",
+                tool.name
+            ));
+        }
+        None => {}
+    }
+
+    let mut step_names = HashSet::new();
+    for step in &tool.workflow {
+        if step.tool.is_some() == step.command.is_some() {
+            return Err(anyhow::anyhow!(
+                "Step '{}' of workflow tool '{}' must specify exactly one of 'tool' or 'command'",
+                step.name,
+                tool.name
+            ));
+        }
+        if !step_names.insert(step.name.as_str()) {
+            return Err(anyhow::anyhow!(
+                "Duplicate step name '{}' in workflow tool '{}'",
+                step.name,
+                tool.name
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// Validate the content sources of a prompt
+fn validate_prompt(prompt: &PromptConfig) -> Result<()> {
+    // Validate that at least one content source is provided
+    let has_content = prompt.content.is_some();
+    let has_path = prompt.path.is_some();
+    let has_url = prompt.url.is_some();
+
+    if prompt.template.is_some() && prompt.command.is_none() {
+        return Err(anyhow::anyhow!(
+            "Prompt '{}' can only use 'template' together with 'command'",
+            prompt.name
+        ));
+    }
+
+    if prompt.command.is_some() {
+        if has_content || has_path || has_url || !prompt.messages.is_empty() {
+            return Err(anyhow::anyhow!(
+                "Prompt '{}' cannot have both 'command' and 'content'/'path'/'url'/'messages' specified",
                 prompt.name
             ));
         }
 
-        if has_path && has_url {
+        return Ok(());
+    }
+
+    if !prompt.messages.is_empty() {
+        if has_content || has_path || has_url {
             return Err(anyhow::anyhow!(
-                "Prompt '{}' cannot have both 'path' and 'url' specified",
+                "Prompt '{}' cannot have both 'messages' and 'content'/'path'/'url' specified",
                 prompt.name
             ));
         }
 
-        prompts.insert(prompt.name.clone(), prompt);
+        for (index, message) in prompt.messages.iter().enumerate() {
+            let sources = [
+                message.content.is_some(),
+                message.path.is_some(),
+                message.resource.is_some(),
+            ]
+            .iter()
+            .filter(|present| **present)
+            .count();
+
+            if sources != 1 {
+                return Err(anyhow::anyhow!(
+                    "Message {} of prompt '{}' must have exactly one of 'content', 'path', or 'resource' specified",
+                    index + 1,
+                    prompt.name
+                ));
+            }
+        }
+
+        return Ok(());
     }
 
-    let mut resources = HashMap::new();
-    for resource in config.resources {
-        if resources.contains_key(&resource.name) {
+    if !has_content && !has_path && !has_url {
+        return Err(anyhow::anyhow!(
+            "Prompt '{}' must have either 'content', 'path', 'url', 'messages', or 'command' specified",
+            prompt.name
+        ));
+    }
+
+    if has_content && (has_path || has_url) {
+        return Err(anyhow::anyhow!(
+            "Prompt '{}' cannot have both 'content' and 'path'/'url' specified",
+            prompt.name
+        ));
+    }
+
+    if has_path && has_url {
+        return Err(anyhow::anyhow!(
+            "Prompt '{}' cannot have both 'path' and 'url' specified",
+            prompt.name
+        ));
+    }
+
+    Ok(())
+}
+
+/// Insert a definition, resolving name clashes with the override policy:
+/// a definition loaded through an `override: true` include is replaced by
+/// one from a regular file, any other clash is an error
+fn insert_definition<T: Definition>(
+    definitions: &mut HashMap<String, T>,
+    kind: &str,
+    definition: T,
+    warnings: &mut Vec<String>,
+) -> Result<()> {
+    if let Some(existing) = definitions.get(definition.name()) {
+        let existing_origin = existing.origin();
+        let new_origin = definition.origin();
+        if existing_origin.overridable != new_origin.overridable {
+            let (kept, replaced) = if existing_origin.overridable {
+                (new_origin, existing_origin)
+            } else {
+                (existing_origin, new_origin)
+            };
+            warnings.push(format!(
+                "{} '{}' from {} is overridden by the definition in {}",
+                kind,
+                definition.name(),
+                replaced.source,
+                kept.source
+            ));
+        }
+        if !existing_origin.overridable && new_origin.overridable {
+            return Ok(());
+        }
+        if existing_origin.overridable == new_origin.overridable {
             return Err(anyhow::anyhow!(
-                "Duplicate resource name: {}",
-                resource.name
+                "Duplicate {} name: {} (defined in {} and {})",
+                kind,
+                definition.name(),
+                existing_origin.source,
+                new_origin.source
             ));
         }
-        resources.insert(resource.name.clone(), resource);
+    }
+
+    definitions.insert(definition.name().to_string(), definition);
+    Ok(())
+}
+
+/// Load and parse the configuration file
+pub async fn load_config(config_path: &str, profile: Option<&str>) -> Result<ConfigData> {
+    let config_content = fs::read_to_string(config_path)
+        .context(format!("Failed to read config file: {config_path}"))?;
+
+    let format = ConfigFormat::detect(config_path, None);
+    let mut config = parse_config(&config_content, config_path, format, true)?;
+
+    let http = HttpClient::new(config.http.clone())?;
+
+    // Keep track of loaded external files to avoid duplicates, and of the
+    // chain of files currently being included to detect cycles
+    let mut loaded_files = HashMap::new();
+    let mut chain = vec![source_key(config_path)];
+
+    let origin = DefinitionOrigin {
+        source: config_path.to_string(),
+        overridable: false,
+    };
+    tag_definitions(&mut config, &origin, "");
+
+    // Load external configurations recursively and merge them, followed by
+    // the fragments of the config directory
+    let mut external_configs = std::mem::take(&mut config.external_configs);
+    external_configs.extend(config_directory_entries(config_path)?);
+    load_includes(
+        &http,
+        IncludeParent {
+            source: config_path,
+            origin: &origin,
+            namespace: "",
+        },
+        &external_configs,
+        &mut chain,
+        &mut loaded_files,
+        &mut config,
+    )
+    .await?;
+
+    let mut warnings = Vec::new();
+
+    let mut skipped = match profile {
+        Some(profile) => apply_profile(&mut config, profile)
+            .context(format!("Failed to apply profile '{profile}'"))?,
+        None => Vec::new(),
+    };
+
+    skipped.extend(apply_conditions(&mut config));
+
+    let mut tools = HashMap::new();
+    for tool in config.tools {
+        validate_tool(&tool).context(format!("Invalid tool defined in {}", tool.origin.source))?;
+        insert_definition(&mut tools, "tool", tool, &mut warnings)?;
+    }
+
+    let mut prompts = HashMap::new();
+    for prompt in config.prompts {
+        validate_prompt(&prompt).context(format!(
+            "Invalid prompt defined in {}",
+            prompt.origin.source
+        ))?;
+        insert_definition(&mut prompts, "prompt", prompt, &mut warnings)?;
+    }
+
+    let mut resources = HashMap::new();
+    for resource in config.resources {
+        insert_definition(&mut resources, "resource", resource, &mut warnings)?;
+    }
+
+    // Validate that workflow steps call configured tools, which cannot be
+    // workflows themselves
+    for tool in tools.values() {
+        for step_tool in tool.workflow.iter().filter_map(|step| step.tool.as_ref()) {
+            match tools.get(step_tool) {
+                None => {
+                    return Err(anyhow::anyhow!(
+                        "Workflow tool '{}' defined in {} references unknown tool: {}",
+                        tool.name,
+                        tool.origin.source,
+                        step_tool
+                    ))
+                }
+                Some(called) if !called.workflow.is_empty() => {
+                    return Err(anyhow::anyhow!(
+                        "Workflow tool '{}' defined in {} cannot call workflow tool: {}",
+                        tool.name,
+                        tool.origin.source,
+                        step_tool
+                    ))
+                }
+                Some(_) => {}
+            }
+        }
+    }
+
+    // Validate that embedded prompt resources and commands refer to configured entries
+    for prompt in prompts.values() {
+        if let Some(command) = &prompt.command {
+            if !tools.contains_key(&command.tool) {
+                return Err(anyhow::anyhow!(
+                    "Prompt '{}' defined in {} references unknown tool: {}",
+                    prompt.name,
+                    prompt.origin.source,
+                    command.tool
+                ));
+            }
+        }
+
+        for message in &prompt.messages {
+            if let Some(resource_name) = &message.resource {
+                if !resources.contains_key(resource_name) {
+                    return Err(anyhow::anyhow!(
+                        "Prompt '{}' defined in {} references unknown resource: {}",
+                        prompt.name,
+                        prompt.origin.source,
+                        resource_name
+                    ));
+                }
+            }
+        }
     }
 
     Ok(ConfigData {
         tools,
         prompts,
         resources,
+        http,
+        warnings,
+        skipped,
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn render_template_substitutes_known_placeholders() {
+        let values = values(&[("name", "world"), ("n", "2")]);
+        assert_eq!(
+            render_template("hello {{name}}, {{n}} times", &values),
+            "hello world, 2 times"
+        );
+        assert_eq!(
+            render_template("{{unknown}} and {{ name", &values),
+            "{{unknown}} and {{ name"
+        );
+    }
+
+    #[test]
+    fn render_template_does_not_expand_inserted_values() {
+        let values = values(&[("a", "{{b}}"), ("b", "{{c}}"), ("c", "{{d}}")]);
+        for _ in 0..5 {
+            assert_eq!(render_template("{{a}} {{b}}", &values), "{{b}} {{c}}");
+        }
+    }
+
+    #[test]
+    fn evaluate_condition_compares_operands() {
+        let values = values(&[("code", "0"), ("out", "x == x"), ("empty", "")]);
+        assert!(evaluate_condition("{{code}} == 0", &values));
+        assert!(evaluate_condition("{{code}} != 1", &values));
+        assert!(!evaluate_condition("{{out}} == y", &values));
+        assert!(evaluate_condition("{{out}} == x == x", &values));
+        assert!(!evaluate_condition("{{empty}}", &values));
+        assert!(!evaluate_condition("false", &values));
+        assert!(evaluate_condition("{{out}}", &values));
+    }
+
+    const PROFILE_CONFIG: &str = r#"
+tools:
+  - name: "list_files"
+    description: "List files"
+    command: "ls"
+    path: "/"
+    accepts_args: true
+    accept_input: false
+  - name: "git_diff"
+    description: "Show git changes"
+    command: "git"
+    path: "/"
+    accepts_args: true
+    accept_input: false
+  - name: "review"
+    description: "Diff, then list"
+    path: "/"
+    accepts_args: false
+    accept_input: false
+    workflow:
+      - name: "diff"
+        tool: "git_diff"
+      - name: "list"
+        tool: "list_files"
+prompts:
+  - name: "review_staged"
+    description: "Review staged changes"
+    command:
+      tool: "git_diff"
+  - name: "review_all"
+    description: "Review through the workflow"
+    command:
+      tool: "review"
+  - name: "listing"
+    description: "Describe the files"
+    command:
+      tool: "list_files"
+profiles:
+  readonly:
+    tools: ["list_files"]
+  review:
+    tools: ["list_files", "review"]
+  listing:
+    tools: ["list_files"]
+    prompts: ["listing", "review_staged"]
+"#;
+
+    /// Write `files` to a temporary directory and load its `config.yaml`
+    async fn load_files(files: &[(&str, &str)], profile: Option<&str>) -> Result<ConfigData> {
+        let dir = tempfile::tempdir().unwrap();
+        for (name, content) in files {
+            let path = dir.path().join(name);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+        }
+        let path = dir.path().join("config.yaml");
+        load_config(path.to_str().unwrap(), profile).await
+    }
+
+    /// A configuration defining one `echo` tool named `name`, then `rest`
+    fn tool_config(name: &str, rest: &str) -> String {
+        format!(
+            "tools:\n  - name: \"{name}\"\n    description: \"Echo\"\n    command: \"echo\"\n    path: \"/\"\n    accepts_args: true\n    accept_input: false\n{rest}"
+        )
+    }
+
+    async fn load_profile(profile: &str) -> Result<ConfigData> {
+        load_files(&[("config.yaml", PROFILE_CONFIG)], Some(profile)).await
+    }
+
+    #[tokio::test]
+    async fn profile_skips_dependents_of_disabled_tools() {
+        let config = load_profile("readonly").await.unwrap();
+        assert!(config.tools.contains_key("list_files"));
+        assert!(!config.tools.contains_key("git_diff"));
+        assert!(!config.tools.contains_key("review"));
+        assert!(config.prompts.contains_key("listing"));
+        assert!(!config.prompts.contains_key("review_staged"));
+        assert!(!config.prompts.contains_key("review_all"));
+        assert_eq!(config.skipped.len(), 2);
+        assert!(config
+            .skipped
+            .iter()
+            .any(|s| s.starts_with("prompt 'review_staged'")
+                && s.ends_with("tool 'git_diff' is not enabled in profile 'readonly'")));
+        assert!(config
+            .skipped
+            .iter()
+            .any(|s| s.starts_with("prompt 'review_all'")
+                && s.ends_with("tool 'review' is not enabled in profile 'readonly'")));
+    }
+
+    #[tokio::test]
+    async fn profile_rejects_listed_dependents_of_disabled_tools() {
+        let Err(error) = load_profile("review").await else {
+            panic!("profile 'review' should be rejected");
+        };
+        let error = format!("{error:#}");
+        assert!(error.contains("Workflow tool 'review'"), "{error}");
+        assert!(error.contains("tool 'git_diff'"), "{error}");
+
+        let Err(error) = load_profile("listing").await else {
+            panic!("profile 'listing' should be rejected");
+        };
+        let error = format!("{error:#}");
+        assert!(
+            error.contains("Failed to apply profile 'listing'"),
+            "{error}"
+        );
+        assert!(error.contains("Prompt 'review_staged'"), "{error}");
+        assert!(error.contains("tool 'git_diff'"), "{error}");
+    }
+
+    fn error_of(result: Result<ConfigData>) -> String {
+        match result {
+            Ok(_) => panic!("configuration should be rejected"),
+            Err(error) => format!("{error:#}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn includes_resolve_relative_to_including_file() {
+        let config = tool_config("main", "external_configs:\n  - \"sub/a.yaml\"\n");
+        let a = tool_config("a", "external_configs:\n  - \"b.yaml\"\n");
+        let b = tool_config("b", "");
+        let files = [
+            ("config.yaml", config.as_str()),
+            ("sub/a.yaml", a.as_str()),
+            ("sub/b.yaml", b.as_str()),
+        ];
+        let config = load_files(&files, None).await.unwrap();
+        assert!(config.tools.contains_key("main"));
+        assert!(config.tools.contains_key("a"));
+        assert!(config.tools.contains_key("b"));
+        assert!(config.tools["b"].origin.source.ends_with("sub/b.yaml"));
+    }
+
+    #[tokio::test]
+    async fn includes_reject_cycles() {
+        let config = tool_config("main", "external_configs:\n  - \"config.yaml\"\n");
+        let error = error_of(load_files(&[("config.yaml", &config)], None).await);
+        assert!(error.contains("Cycle detected"), "{error}");
+
+        let config = tool_config("main", "external_configs:\n  - \"a.yaml\"\n");
+        let a = tool_config("a", "external_configs:\n  - \"config.yaml\"\n");
+        let files = [("config.yaml", config.as_str()), ("a.yaml", a.as_str())];
+        let error = error_of(load_files(&files, None).await);
+        assert!(error.contains("Cycle detected"), "{error}");
+    }
+
+    #[tokio::test]
+    async fn includes_reject_deep_nesting() {
+        let levels = MAX_INCLUDE_DEPTH + 2;
+        let mut contents = vec![tool_config(
+            "main",
+            "external_configs:\n  - \"level1.yaml\"\n",
+        )];
+        for level in 1..levels {
+            contents.push(tool_config(
+                &format!("tool{level}"),
+                &format!("external_configs:\n  - \"level{}.yaml\"\n", level + 1),
+            ));
+        }
+        contents.push(tool_config(&format!("tool{levels}"), ""));
+        let names: Vec<String> = (0..=levels)
+            .map(|level| match level {
+                0 => "config.yaml".to_string(),
+                _ => format!("level{level}.yaml"),
+            })
+            .collect();
+        let files: Vec<(&str, &str)> = names
+            .iter()
+            .zip(&contents)
+            .map(|(name, content)| (name.as_str(), content.as_str()))
+            .collect();
+        let error = error_of(load_files(&files, None).await);
+        assert!(error.contains("nested deeper than"), "{error}");
+
+        // Nesting up to the limit loads
+        let shallow = &files[files.len() - MAX_INCLUDE_DEPTH..];
+        let mut files = shallow.to_vec();
+        let root = tool_config(
+            "main",
+            &format!("external_configs:\n  - \"{}\"\n", files[0].0),
+        );
+        files.push(("config.yaml", &root));
+        assert!(load_files(&files, None).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn duplicate_names_are_rejected() {
+        let config = tool_config("status", "external_configs:\n  - \"shared.yaml\"\n");
+        let shared = tool_config("status", "");
+        let files = [
+            ("config.yaml", config.as_str()),
+            ("shared.yaml", shared.as_str()),
+        ];
+        let error = error_of(load_files(&files, None).await);
+        assert!(error.contains("Duplicate tool name: status"), "{error}");
+        assert!(error.contains("config.yaml"), "{error}");
+        assert!(error.contains("shared.yaml"), "{error}");
+    }
+
+    #[tokio::test]
+    async fn override_lets_other_files_replace_definitions() {
+        // The overridable definition loses whichever file is merged first
+        let config = tool_config(
+            "status",
+            "external_configs:\n  - source: \"shared.yaml\"\n    override: true\n",
+        );
+        let shared = tool_config("status", "").replace("command: \"echo\"", "command: \"true\"");
+        let files = [
+            ("config.yaml", config.as_str()),
+            ("shared.yaml", shared.as_str()),
+        ];
+        let config = load_files(&files, None).await.unwrap();
+        assert_eq!(config.tools["status"].command, "echo");
+        assert_eq!(config.warnings.len(), 1);
+        assert!(
+            config.warnings[0].starts_with("tool 'status' from"),
+            "{}",
+            config.warnings[0]
+        );
+    }
+
+    #[tokio::test]
+    async fn namespace_prefixes_names_and_references() {
+        let config = tool_config(
+            "status",
+            "external_configs:\n  - source: \"git.yaml\"\n    namespace: \"git\"\n",
+        );
+        let git = tool_config(
+            "status",
+            "prompts:\n  - name: \"review\"\n    description: \"Review\"\n    command:\n      tool: \"status\"\n",
+        );
+        let files = [("config.yaml", config.as_str()), ("git.yaml", git.as_str())];
+        let config = load_files(&files, None).await.unwrap();
+        assert!(config.tools.contains_key("status"));
+        assert!(config.tools.contains_key("git.status"));
+        let prompt = &config.prompts["git.review"];
+        assert_eq!(prompt.command.as_ref().unwrap().tool, "git.status");
+    }
+
+    #[test]
+    fn config_directory_lists_config_files_in_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let fragments = dir.path().join(CONFIG_DIRECTORY);
+        fs::create_dir_all(fragments.join("40-nested.yaml")).unwrap();
+        for name in [
+            "20-docker.toml",
+            "10-git.yaml",
+            "50-team.JSON",
+            "30-extra.yml",
+            ".hidden.yaml",
+            "notes.txt",
+            "README",
+        ] {
+            fs::write(fragments.join(name), "").unwrap();
+        }
+        let config_path = dir.path().join("config.yaml");
+        let entries = config_directory_entries(config_path.to_str().unwrap()).unwrap();
+        let sources: Vec<&str> = entries.iter().map(|e| e.source.as_str()).collect();
+        assert_eq!(
+            sources,
+            [
+                "mycommand-tools.d/10-git.yaml",
+                "mycommand-tools.d/20-docker.toml",
+                "mycommand-tools.d/30-extra.yml",
+                "mycommand-tools.d/50-team.JSON",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn config_directory_fragments_are_loaded() {
+        let config = tool_config("main", "");
+        let git = tool_config("git_status", "");
+        let files = [
+            ("config.yaml", config.as_str()),
+            ("mycommand-tools.d/10-git.yaml", git.as_str()),
+            ("mycommand-tools.d/notes.txt", "not a config: ["),
+        ];
+        let config = load_files(&files, None).await.unwrap();
+        assert!(config.tools.contains_key("main"));
+        assert!(config.tools["git_status"]
+            .origin
+            .source
+            .ends_with("mycommand-tools.d/10-git.yaml"));
+    }
+
+    #[tokio::test]
+    async fn conditions_skip_tools_and_dependents() {
+        let config = r#"
+tools:
+  - name: "memory"
+    description: "Memory usage"
+    command: "free"
+    path: "/"
+    accepts_args: false
+    accept_input: false
+    when:
+      os: "plan9"
+  - name: "memory"
+    description: "Memory usage"
+    command: "echo"
+    path: "/"
+    accepts_args: false
+    accept_input: false
+  - name: "fetch"
+    description: "Fetch a page"
+    command: "mycommandmcp-no-such-command"
+    path: "/"
+    accepts_args: true
+    accept_input: false
+    when:
+      command: "mycommandmcp-no-such-command"
+prompts:
+  - name: "page"
+    description: "Summarize a page"
+    command:
+      tool: "fetch"
+"#;
+        let config = load_files(&[("config.yaml", config)], None).await.unwrap();
+        assert_eq!(config.tools["memory"].command, "echo");
+        assert!(!config.tools.contains_key("fetch"));
+        assert!(!config.prompts.contains_key("page"));
+        assert_eq!(config.skipped.len(), 3);
+        assert!(
+            config.skipped[0].contains("requires os plan9"),
+            "{:?}",
+            config.skipped
+        );
+        assert!(config.skipped.iter().any(
+            |s| s.starts_with("prompt 'page'") && s.ends_with("tool 'fetch' is not available")
+        ));
+    }
+}
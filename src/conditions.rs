@@ -0,0 +1,159 @@
+use schemars::JsonSchema;
+use serde::Deserialize;
+use std::env;
+use std::path::Path;
+
+use crate::validate::find_in_path;
+
+/// A single value or a list of values
+#[derive(Debug, Deserialize, Clone, JsonSchema)]
+#[serde(untagged)]
+pub enum OneOrMany {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl OneOrMany {
+    pub fn values(&self) -> &[String] {
+        match self {
+            OneOrMany::One(value) => std::slice::from_ref(value),
+            OneOrMany::Many(values) => values,
+        }
+    }
+}
+
+/// Conditions that must all hold for a tool to be available on this machine
+#[derive(Debug, Deserialize, Clone, Default, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct ToolCondition {
+    /// Operating systems the tool runs on, e.g. `linux`, `macos` or `windows`
+    #[serde(default)]
+    pub os: Option<OneOrMany>,
+    /// CPU architectures the tool runs on, e.g. `x86_64` or `aarch64`
+    #[serde(default)]
+    pub arch: Option<OneOrMany>,
+    /// Executables that must be found on PATH
+    #[serde(default)]
+    pub command: Option<OneOrMany>,
+    /// Environment variables that must be set
+    #[serde(default)]
+    pub env: Option<OneOrMany>,
+    /// Files or directories that must exist
+    #[serde(default)]
+    pub file: Option<OneOrMany>,
+}
+
+impl ToolCondition {
+    /// Check the conditions, returning the reason of the first one that fails
+    pub fn check(&self) -> Result<(), String> {
+        if let Some(os) = &self.os {
+            if !os.values().iter().any(|os| os == env::consts::OS) {
+                return Err(format!(
+                    "requires os {}, running on {}",
+                    os.values().join(" or "),
+                    env::consts::OS
+                ));
+            }
+        }
+
+        if let Some(arch) = &self.arch {
+            if !arch.values().iter().any(|arch| arch == env::consts::ARCH) {
+                return Err(format!(
+                    "requires arch {}, running on {}",
+                    arch.values().join(" or "),
+                    env::consts::ARCH
+                ));
+            }
+        }
+
+        for command in self.command.iter().flat_map(|c| c.values()) {
+            if find_in_path(command).is_none() {
+                return Err(format!("command '{command}' was not found on PATH"));
+            }
+        }
+
+        for name in self.env.iter().flat_map(|e| e.values()) {
+            if env::var_os(name).is_none() {
+                return Err(format!("environment variable '{name}' is not set"));
+            }
+        }
+
+        for file in self.file.iter().flat_map(|f| f.values()) {
+            if !Path::new(file).exists() {
+                return Err(format!("file '{file}' does not exist"));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn one(value: &str) -> Option<OneOrMany> {
+        Some(OneOrMany::One(value.to_string()))
+    }
+
+    #[test]
+    fn platform_conditions_match_current_platform() {
+        let condition = ToolCondition {
+            os: Some(OneOrMany::Many(vec![
+                "plan9".to_string(),
+                env::consts::OS.to_string(),
+            ])),
+            arch: one(env::consts::ARCH),
+            ..ToolCondition::default()
+        };
+        assert_eq!(condition.check(), Ok(()));
+
+        let condition = ToolCondition {
+            os: one("plan9"),
+            ..ToolCondition::default()
+        };
+        let reason = condition.check().unwrap_err();
+        assert!(reason.starts_with("requires os plan9"), "{reason}");
+
+        let condition = ToolCondition {
+            arch: one("pdp11"),
+            ..ToolCondition::default()
+        };
+        assert!(condition.check().is_err());
+    }
+
+    #[test]
+    fn command_env_and_file_conditions() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().to_str().unwrap();
+        let condition = ToolCondition {
+            command: one("sh"),
+            env: one("PATH"),
+            file: one(path),
+            ..ToolCondition::default()
+        };
+        assert_eq!(condition.check(), Ok(()));
+
+        let condition = ToolCondition {
+            command: one("mycommandmcp-no-such-command"),
+            ..ToolCondition::default()
+        };
+        assert_eq!(
+            condition.check(),
+            Err("command 'mycommandmcp-no-such-command' was not found on PATH".to_string())
+        );
+
+        let condition = ToolCondition {
+            env: one("MYCOMMANDMCP_NO_SUCH_VARIABLE"),
+            ..ToolCondition::default()
+        };
+        assert!(condition.check().is_err());
+
+        let missing = dir.path().join("missing");
+        let condition = ToolCondition {
+            file: one(missing.to_str().unwrap()),
+            ..ToolCondition::default()
+        };
+        assert!(condition.check().is_err());
+    }
+}
@@ -0,0 +1,331 @@
+use anyhow::{Context, Result};
+use regex::Regex;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::HashSet;
+
+/// A post-processing step applied to the captured stdout of a tool
+#[derive(Debug, Deserialize, Clone, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputFilter {
+    /// Remove ANSI escape sequences such as colors
+    StripAnsi,
+    /// Keep only the lines matching a regular expression
+    Grep(String),
+    /// Drop the lines matching a regular expression
+    Exclude(String),
+    /// Keep the first N lines
+    Head(usize),
+    /// Keep the last N lines
+    Tail(usize),
+    /// Drop repeated lines, keeping the first occurrence
+    Dedupe,
+    /// Select a value of JSON output with a JSON pointer, e.g. `/items/0/name`
+    JsonPointer(String),
+    /// Select values of JSON output with a jq-style path, e.g. `.items[].name`
+    JsonPath(String),
+    /// Truncate lines longer than N characters
+    MaxLineWidth(usize),
+}
+
+impl OutputFilter {
+    pub fn name(&self) -> &'static str {
+        match self {
+            OutputFilter::StripAnsi => "strip_ansi",
+            OutputFilter::Grep(_) => "grep",
+            OutputFilter::Exclude(_) => "exclude",
+            OutputFilter::Head(_) => "head",
+            OutputFilter::Tail(_) => "tail",
+            OutputFilter::Dedupe => "dedupe",
+            OutputFilter::JsonPointer(_) => "json_pointer",
+            OutputFilter::JsonPath(_) => "json_path",
+            OutputFilter::MaxLineWidth(_) => "max_line_width",
+        }
+    }
+
+    /// Check the filter settings, such as regular expressions and paths
+    pub fn validate(&self) -> Result<()> {
+        match self {
+            OutputFilter::Grep(pattern) | OutputFilter::Exclude(pattern) => {
+                Regex::new(pattern).context(format!("Invalid regex: {pattern}"))?;
+            }
+            OutputFilter::JsonPointer(pointer)
+                if !pointer.is_empty() && !pointer.starts_with('/') =>
+            {
+                return Err(anyhow::anyhow!(
+                    "JSON pointer must be empty or start with '/': {pointer}"
+                ));
+            }
+            OutputFilter::JsonPath(path) => {
+                parse_json_path(path)?;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Apply the filter to the output of a command
+    pub fn apply(&self, output: &str) -> Result<String> {
+        let lines = || output.lines();
+        let filtered = match self {
+            OutputFilter::StripAnsi => return Ok(strip_ansi(output)),
+            OutputFilter::Grep(pattern) => {
+                let regex = Regex::new(pattern)?;
+                join(lines().filter(|line| regex.is_match(line)))
+            }
+            OutputFilter::Exclude(pattern) => {
+                let regex = Regex::new(pattern)?;
+                join(lines().filter(|line| !regex.is_match(line)))
+            }
+            OutputFilter::Head(count) => join(lines().take(*count)),
+            OutputFilter::Tail(count) => {
+                let total = lines().count();
+                join(lines().skip(total.saturating_sub(*count)))
+            }
+            OutputFilter::Dedupe => {
+                let mut seen = HashSet::new();
+                join(lines().filter(|line| seen.insert(*line)))
+            }
+            OutputFilter::JsonPointer(pointer) => {
+                let value: Value = serde_json::from_str(output).context("Invalid JSON")?;
+                let selected = value
+                    .pointer(pointer)
+                    .context(format!("No value at JSON pointer: {pointer}"))?;
+                format_json(selected)
+            }
+            OutputFilter::JsonPath(path) => {
+                let value: Value = serde_json::from_str(output).context("Invalid JSON")?;
+                let mut selected = vec![&value];
+                for segment in parse_json_path(path)? {
+                    selected = segment.select(selected);
+                }
+                join(selected.into_iter().map(format_json))
+            }
+            OutputFilter::MaxLineWidth(width) => {
+                join(lines().map(|line| match line.char_indices().nth(*width) {
+                    Some((end, _)) => line[..end].to_string(),
+                    None => line.to_string(),
+                }))
+            }
+        };
+        Ok(filtered)
+    }
+}
+
+/// Join lines, keeping a trailing newline when there is any output
+fn join<I, S>(lines: I) -> String
+where
+    I: Iterator<Item = S>,
+    S: AsRef<str>,
+{
+    let mut joined = String::new();
+    for line in lines {
+        joined.push_str(line.as_ref());
+        joined.push('\n');
+    }
+    joined
+}
+
+/// Print strings without quotes, like `jq -r`, and other values as JSON
+fn format_json(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// Remove CSI sequences (colors, cursor movement) and OSC sequences (titles, links)
+fn strip_ansi(text: &str) -> String {
+    let mut stripped = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\u{1b}' {
+            stripped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('[') => {
+                // Parameters and intermediates, up to a final byte in @..~
+                for c in chars.by_ref() {
+                    if ('@'..='~').contains(&c) {
+                        break;
+                    }
+                }
+            }
+            Some(']') => {
+                // Terminated by BEL or ESC \
+                while let Some(c) = chars.next() {
+                    if c == '\u{7}' {
+                        break;
+                    }
+                    if c == '\u{1b}' && chars.peek() == Some(&'\\') {
+                        chars.next();
+                        break;
+                    }
+                }
+            }
+            // Two-character sequences such as ESC ( B
+            _ => {}
+        }
+    }
+    stripped
+}
+
+/// A segment of a jq-style path
+#[derive(Debug)]
+enum PathSegment {
+    Field(String),
+    Index(usize),
+    Iterate,
+}
+
+impl PathSegment {
+    fn select<'a>(&self, values: Vec<&'a Value>) -> Vec<&'a Value> {
+        values
+            .into_iter()
+            .flat_map(|value| -> Vec<&'a Value> {
+                match self {
+                    PathSegment::Field(name) => value.get(name).into_iter().collect(),
+                    PathSegment::Index(index) => value.get(index).into_iter().collect(),
+                    PathSegment::Iterate => match value {
+                        Value::Array(items) => items.iter().collect(),
+                        Value::Object(map) => map.values().collect(),
+                        _ => Vec::new(),
+                    },
+                }
+            })
+            .collect()
+    }
+}
+
+/// Parse a jq-style path such as `.items[].name` or `.[0].id`
+fn parse_json_path(path: &str) -> Result<Vec<PathSegment>> {
+    let invalid = || anyhow::anyhow!("Invalid JSON path: {path}");
+    let rest = path.strip_prefix('.').ok_or_else(invalid)?;
+
+    let mut segments = Vec::new();
+    let mut field = String::new();
+    let mut chars = rest.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '.' | '[' => {
+                if !field.is_empty() {
+                    segments.push(PathSegment::Field(std::mem::take(&mut field)));
+                }
+                if c == '[' {
+                    let mut index = String::new();
+                    let mut closed = false;
+                    for c in chars.by_ref() {
+                        if c == ']' {
+                            closed = true;
+                            break;
+                        }
+                        index.push(c);
+                    }
+                    if !closed {
+                        return Err(invalid());
+                    }
+                    if index.is_empty() {
+                        segments.push(PathSegment::Iterate);
+                    } else {
+                        segments.push(PathSegment::Index(
+                            index.trim().parse().map_err(|_| invalid())?,
+                        ));
+                    }
+                }
+            }
+            ']' => return Err(invalid()),
+            _ => field.push(c),
+        }
+    }
+    if !field.is_empty() {
+        segments.push(PathSegment::Field(field));
+    }
+
+    Ok(segments)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn line_filters() {
+        let output = "alpha\nbeta\nalpha\ngamma\n";
+        let apply = |filter: OutputFilter| filter.apply(output).unwrap();
+
+        assert_eq!(apply(OutputFilter::Grep("^a".into())), "alpha\nalpha\n");
+        assert_eq!(apply(OutputFilter::Exclude("a$".into())), "");
+        assert_eq!(apply(OutputFilter::Head(2)), "alpha\nbeta\n");
+        assert_eq!(apply(OutputFilter::Tail(2)), "alpha\ngamma\n");
+        assert_eq!(apply(OutputFilter::Tail(10)), output);
+        assert_eq!(apply(OutputFilter::Dedupe), "alpha\nbeta\ngamma\n");
+        assert_eq!(apply(OutputFilter::MaxLineWidth(3)), "alp\nbet\nalp\ngam\n");
+    }
+
+    #[test]
+    fn max_line_width_keeps_characters_whole() {
+        let filtered = OutputFilter::MaxLineWidth(2).apply("héllo\n").unwrap();
+        assert_eq!(filtered, "hé\n");
+    }
+
+    #[test]
+    fn strip_ansi_removes_csi_and_osc_sequences() {
+        let colored =
+            "\u{1b}[1;31merror\u{1b}[0m: \u{1b}]8;;http://x\u{7}link\u{1b}]8;;\u{1b}\\ done";
+        assert_eq!(strip_ansi(colored), "error: link done");
+    }
+
+    #[test]
+    fn json_pointer_selects_a_value() {
+        let output = r#"{"items": [{"name": "a"}, {"name": "b"}]}"#;
+        let filter = OutputFilter::JsonPointer("/items/1/name".into());
+        assert_eq!(filter.apply(output).unwrap(), "b");
+
+        let missing = OutputFilter::JsonPointer("/nothing".into());
+        assert!(missing.apply(output).is_err());
+        assert!(OutputFilter::JsonPointer("items".into())
+            .validate()
+            .is_err());
+    }
+
+    #[test]
+    fn json_path_selects_values() {
+        let output = r#"{"items": [{"name": "a", "id": 1}, {"name": "b", "id": 2}]}"#;
+        let apply = |path: &str| OutputFilter::JsonPath(path.into()).apply(output).unwrap();
+
+        assert_eq!(apply(".items[].name"), "a\nb\n");
+        assert_eq!(apply(".items[1].id"), "2\n");
+        assert_eq!(apply(".items[0]"), "{\"id\":1,\"name\":\"a\"}\n");
+        assert_eq!(apply(".missing"), "");
+    }
+
+    #[test]
+    fn parse_json_path_segments() {
+        let segments = parse_json_path(".items[].tags[0]").unwrap();
+        assert!(matches!(
+            segments.as_slice(),
+            [
+                PathSegment::Field(items),
+                PathSegment::Iterate,
+                PathSegment::Field(tags),
+                PathSegment::Index(0)
+            ] if items == "items" && tags == "tags"
+        ));
+        assert!(parse_json_path(".").unwrap().is_empty());
+    }
+
+    #[test]
+    fn parse_json_path_rejects_invalid_paths() {
+        for path in ["items", ".items[", ".items]", ".items[x]"] {
+            assert!(parse_json_path(path).is_err(), "{path} should be invalid");
+        }
+    }
+
+    #[test]
+    fn validate_rejects_invalid_regex() {
+        assert!(OutputFilter::Grep("(".into()).validate().is_err());
+        assert!(OutputFilter::Exclude("ok".into()).validate().is_ok());
+    }
+}
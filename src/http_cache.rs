@@ -0,0 +1,392 @@
+use anyhow::{Context, Result};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::http_client::HttpResponse;
+
+/// On-disk HTTP cache settings
+#[derive(Debug, Deserialize, Clone, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct CacheConfig {
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    /// Cache directory, defaults to `mycommandmcp/http` under the user cache dir
+    #[serde(default)]
+    pub dir: Option<String>,
+    /// Freshness lifetime for responses without a Cache-Control max-age
+    #[serde(default)]
+    pub ttl_secs: u64,
+    #[serde(default = "default_max_size_bytes")]
+    pub max_size_bytes: u64,
+    /// Serve stale entries when the network fails
+    #[serde(default)]
+    pub offline: bool,
+    /// Also cache responses fetched with headers, credentials or a client certificate
+    #[serde(default)]
+    pub credentialed: bool,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+fn default_max_size_bytes() -> u64 {
+    100 * 1024 * 1024
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        CacheConfig {
+            enabled: default_enabled(),
+            dir: None,
+            ttl_secs: 0,
+            max_size_bytes: default_max_size_bytes(),
+            offline: false,
+            credentialed: false,
+        }
+    }
+}
+
+/// Metadata stored next to each cached body
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CacheMetadata {
+    pub url: String,
+    pub content_type: Option<String>,
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+    pub stored_at: u64,
+    pub max_age: Option<u64>,
+    pub no_cache: bool,
+}
+
+pub struct CacheEntry {
+    pub metadata: CacheMetadata,
+    pub body: Vec<u8>,
+}
+
+impl CacheEntry {
+    pub fn into_response(self) -> HttpResponse {
+        HttpResponse {
+            body: self.body,
+            content_type: self.metadata.content_type,
+            etag: self.metadata.etag,
+            last_modified: self.metadata.last_modified,
+            cache_control: None,
+        }
+    }
+}
+
+/// Directives parsed from a Cache-Control header
+#[derive(Debug, Default)]
+pub struct CacheDirectives {
+    pub no_store: bool,
+    pub no_cache: bool,
+    pub max_age: Option<u64>,
+}
+
+impl CacheDirectives {
+    pub fn parse(header: Option<&str>) -> Self {
+        let mut directives = CacheDirectives::default();
+        for directive in header.unwrap_or_default().split(',') {
+            let directive = directive.trim().to_ascii_lowercase();
+            if directive == "no-store" {
+                directives.no_store = true;
+            } else if directive == "no-cache" {
+                directives.no_cache = true;
+            } else if let Some(value) = directive.strip_prefix("max-age=") {
+                directives.max_age = value.trim_matches('"').parse().ok();
+            }
+        }
+        directives
+    }
+}
+
+/// Cache of HTTP responses keyed by URL and by the headers and credentials
+/// they were fetched with
+#[derive(Clone)]
+pub struct HttpCache {
+    dir: PathBuf,
+    config: CacheConfig,
+}
+
+impl HttpCache {
+    /// Create the cache, or return `None` when caching is disabled or no cache dir is available
+    pub fn new(config: CacheConfig) -> Option<Self> {
+        if !config.enabled {
+            return None;
+        }
+
+        let dir = match &config.dir {
+            Some(dir) => PathBuf::from(dir),
+            None => dirs::cache_dir()?.join("mycommandmcp").join("http"),
+        };
+
+        Some(HttpCache { dir, config })
+    }
+
+    pub fn offline(&self) -> bool {
+        self.config.offline
+    }
+
+    /// Whether responses fetched with headers, credentials or a client
+    /// certificate are cached
+    pub fn credentialed(&self) -> bool {
+        self.config.credentialed
+    }
+
+    /// Cache key of a URL fetched with the given source fingerprint, which is
+    /// empty for anonymous requests
+    pub fn key(url: &str, fingerprint: &str) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(url.as_bytes());
+        if !fingerprint.is_empty() {
+            hasher.update(b"\n");
+            hasher.update(fingerprint.as_bytes());
+        }
+        format!("{:x}", hasher.finalize())
+    }
+
+    fn body_path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{key}.body"))
+    }
+
+    fn metadata_path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{key}.json"))
+    }
+
+    /// Look up the cached entry for a URL under its cache key
+    pub fn get(&self, key: &str, url: &str) -> Option<CacheEntry> {
+        let metadata = fs::read_to_string(self.metadata_path(key)).ok()?;
+        let metadata: CacheMetadata = serde_json::from_str(&metadata).ok()?;
+        if metadata.url != url {
+            return None;
+        }
+        let body = fs::read(self.body_path(key)).ok()?;
+        Some(CacheEntry { metadata, body })
+    }
+
+    /// Check whether an entry can be served without revalidation
+    pub fn is_fresh(&self, entry: &CacheEntry) -> bool {
+        if entry.metadata.no_cache {
+            return false;
+        }
+        let lifetime = entry.metadata.max_age.unwrap_or(self.config.ttl_secs);
+        now().saturating_sub(entry.metadata.stored_at) < lifetime
+    }
+
+    /// Store a response, honoring its Cache-Control directives
+    pub fn store(&self, key: &str, url: &str, response: &HttpResponse) -> Result<()> {
+        let directives = CacheDirectives::parse(response.cache_control.as_deref());
+        if directives.no_store {
+            return Ok(());
+        }
+
+        create_private_dir(&self.dir).context(format!(
+            "Failed to create cache directory: {}",
+            self.dir.display()
+        ))?;
+
+        let metadata = CacheMetadata {
+            url: url.to_string(),
+            content_type: response.content_type.clone(),
+            etag: response.etag.clone(),
+            last_modified: response.last_modified.clone(),
+            stored_at: now(),
+            max_age: directives.max_age,
+            no_cache: directives.no_cache,
+        };
+
+        write_private(&self.body_path(key), &response.body)?;
+        write_private(
+            &self.metadata_path(key),
+            serde_json::to_string(&metadata)?.as_bytes(),
+        )?;
+
+        self.enforce_max_size()
+    }
+
+    /// Mark an entry as revalidated after a 304 Not Modified response
+    pub fn refresh(
+        &self,
+        key: &str,
+        entry: &mut CacheEntry,
+        cache_control: Option<&str>,
+    ) -> Result<()> {
+        let directives = CacheDirectives::parse(cache_control);
+        entry.metadata.stored_at = now();
+        if cache_control.is_some() {
+            entry.metadata.max_age = directives.max_age;
+            entry.metadata.no_cache = directives.no_cache;
+        }
+        write_private(
+            &self.metadata_path(key),
+            serde_json::to_string(&entry.metadata)?.as_bytes(),
+        )?;
+        Ok(())
+    }
+
+    /// Evict the least recently stored entries until the cache fits its maximum size
+    fn enforce_max_size(&self) -> Result<()> {
+        let mut entries = Vec::new();
+        let mut total = 0;
+        for dir_entry in fs::read_dir(&self.dir)? {
+            let path = dir_entry?.path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some("json") {
+                continue;
+            }
+            let body_path = path.with_extension("body");
+            let size = fs::metadata(&body_path).map(|m| m.len()).unwrap_or(0);
+            let stored_at = fs::read_to_string(&path)
+                .ok()
+                .and_then(|m| serde_json::from_str::<CacheMetadata>(&m).ok())
+                .map(|m| m.stored_at)
+                .unwrap_or(0);
+            total += size;
+            entries.push((stored_at, size, path, body_path));
+        }
+
+        entries.sort_by_key(|(stored_at, ..)| *stored_at);
+        for (_, size, metadata_path, body_path) in entries {
+            if total <= self.config.max_size_bytes {
+                break;
+            }
+            let _ = fs::remove_file(metadata_path);
+            let _ = fs::remove_file(body_path);
+            total -= size;
+        }
+
+        Ok(())
+    }
+}
+
+/// Create the cache directory, readable only by the current user on Unix
+fn create_private_dir(dir: &Path) -> std::io::Result<()> {
+    let mut builder = fs::DirBuilder::new();
+    builder.recursive(true);
+    #[cfg(unix)]
+    std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+    builder.create(dir)
+}
+
+/// Write a cache file readable only by the current user on Unix, as cached
+/// bodies can hold private content
+fn write_private(path: &Path, content: &[u8]) -> std::io::Result<()> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let mut file = options.open(path)?;
+    // Files created by earlier versions keep their mode when truncated
+    #[cfg(unix)]
+    file.set_permissions(std::os::unix::fs::PermissionsExt::from_mode(0o600))?;
+    file.write_all(content)
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn response(body: &str, cache_control: Option<&str>) -> HttpResponse {
+        HttpResponse {
+            body: body.as_bytes().to_vec(),
+            content_type: Some("text/plain".to_string()),
+            etag: Some("\"v1\"".to_string()),
+            last_modified: None,
+            cache_control: cache_control.map(str::to_string),
+        }
+    }
+
+    fn cache(dir: &Path) -> HttpCache {
+        HttpCache::new(CacheConfig {
+            dir: Some(dir.to_string_lossy().to_string()),
+            ttl_secs: 60,
+            ..CacheConfig::default()
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn parses_cache_control_directives() {
+        let directives = CacheDirectives::parse(Some("public, Max-Age=\"300\", no-cache"));
+        assert_eq!(directives.max_age, Some(300));
+        assert!(directives.no_cache);
+        assert!(!directives.no_store);
+
+        assert!(CacheDirectives::parse(Some("no-store")).no_store);
+        assert_eq!(CacheDirectives::parse(Some("max-age=soon")).max_age, None);
+        assert_eq!(CacheDirectives::parse(None).max_age, None);
+    }
+
+    #[test]
+    fn keys_differ_by_fingerprint() {
+        let url = "https://example.com/a";
+        assert_eq!(HttpCache::key(url, ""), HttpCache::key(url, ""));
+        assert_ne!(HttpCache::key(url, ""), HttpCache::key(url, "abc"));
+        assert_ne!(HttpCache::key(url, "abc"), HttpCache::key(url, "def"));
+    }
+
+    #[test]
+    fn stores_and_serves_fresh_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = cache(dir.path());
+        let url = "https://example.com/a";
+        let key = HttpCache::key(url, "");
+
+        cache.store(&key, url, &response("hello", None)).unwrap();
+        let entry = cache.get(&key, url).unwrap();
+        assert_eq!(entry.body, b"hello");
+        assert!(cache.is_fresh(&entry));
+
+        // Another source's key or a colliding URL does not match
+        assert!(cache.get(&HttpCache::key(url, "abc"), url).is_none());
+        assert!(cache.get(&key, "https://example.com/b").is_none());
+    }
+
+    #[test]
+    fn honors_no_store_and_no_cache() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = cache(dir.path());
+        let url = "https://example.com/a";
+        let key = HttpCache::key(url, "");
+
+        cache
+            .store(&key, url, &response("secret", Some("no-store")))
+            .unwrap();
+        assert!(cache.get(&key, url).is_none());
+
+        cache
+            .store(&key, url, &response("stale", Some("no-cache")))
+            .unwrap();
+        assert!(!cache.is_fresh(&cache.get(&key, url).unwrap()));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn cache_files_are_private() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let cache_dir = dir.path().join("http");
+        let cache = cache(&cache_dir);
+        let url = "https://example.com/a";
+        let key = HttpCache::key(url, "");
+        cache.store(&key, url, &response("hello", None)).unwrap();
+
+        let mode = |path: &Path| fs::metadata(path).unwrap().permissions().mode() & 0o777;
+        assert_eq!(mode(&cache_dir), 0o700);
+        assert_eq!(mode(&cache.body_path(&key)), 0o600);
+        assert_eq!(mode(&cache.metadata_path(&key)), 0o600);
+    }
+}
@@ -0,0 +1,563 @@
+use anyhow::{Context, Result};
+use schemars::JsonSchema;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::http_cache::{CacheConfig, CacheMetadata, HttpCache};
+
+/// Upper bound of the delay between two retries
+const MAX_RETRY_BACKOFF_MS: u64 = 60_000;
+
+/// HTTP settings shared by URL prompts, URL resources and external configs
+#[derive(Debug, Deserialize, Clone, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct HttpConfig {
+    #[serde(default = "default_timeout_secs")]
+    pub timeout_secs: u64,
+    #[serde(default = "default_connect_timeout_secs")]
+    pub connect_timeout_secs: u64,
+    #[serde(default = "default_retries")]
+    pub retries: u32,
+    #[serde(default = "default_retry_backoff_ms")]
+    pub retry_backoff_ms: u64,
+    #[serde(default = "default_max_size_bytes")]
+    pub max_size_bytes: u64,
+    #[serde(default)]
+    pub proxy: Option<String>,
+    #[serde(default)]
+    pub no_proxy: Option<String>,
+    #[serde(default)]
+    pub cache: CacheConfig,
+}
+
+fn default_timeout_secs() -> u64 {
+    30
+}
+
+fn default_connect_timeout_secs() -> u64 {
+    10
+}
+
+fn default_retries() -> u32 {
+    2
+}
+
+fn default_retry_backoff_ms() -> u64 {
+    500
+}
+
+fn default_max_size_bytes() -> u64 {
+    10 * 1024 * 1024
+}
+
+impl Default for HttpConfig {
+    fn default() -> Self {
+        HttpConfig {
+            timeout_secs: default_timeout_secs(),
+            connect_timeout_secs: default_connect_timeout_secs(),
+            retries: default_retries(),
+            retry_backoff_ms: default_retry_backoff_ms(),
+            max_size_bytes: default_max_size_bytes(),
+            proxy: None,
+            no_proxy: None,
+            cache: CacheConfig::default(),
+        }
+    }
+}
+
+/// Credentials for a URL source
+#[derive(Deserialize, Clone, JsonSchema)]
+#[serde(tag = "type", rename_all = "lowercase", deny_unknown_fields)]
+pub enum HttpAuth {
+    Basic {
+        username: String,
+        #[serde(default)]
+        password: Option<String>,
+    },
+    Bearer {
+        token: String,
+    },
+}
+
+impl fmt::Debug for HttpAuth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HttpAuth::Basic { username, .. } => f
+                .debug_struct("Basic")
+                .field("username", username)
+                .field("password", &REDACTED)
+                .finish(),
+            HttpAuth::Bearer { .. } => f.debug_struct("Bearer").field("token", &REDACTED).finish(),
+        }
+    }
+}
+
+/// Client TLS certificate options for a URL source, as PEM file paths
+#[derive(Debug, Deserialize, Clone, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct TlsConfig {
+    pub client_cert: String,
+    pub client_key: String,
+    #[serde(default)]
+    pub ca_cert: Option<String>,
+}
+
+/// Contents of the PEM files of a `TlsConfig`
+struct TlsFiles {
+    cert: Vec<u8>,
+    key: Vec<u8>,
+    ca_cert: Option<Vec<u8>>,
+}
+
+impl TlsFiles {
+    fn read(tls: &TlsConfig) -> Result<Self> {
+        let cert = fs::read(&tls.client_cert).context(format!(
+            "Failed to read client certificate: {}",
+            tls.client_cert
+        ))?;
+        let key = fs::read(&tls.client_key)
+            .context(format!("Failed to read client key: {}", tls.client_key))?;
+        let ca_cert = tls
+            .ca_cert
+            .as_ref()
+            .map(|ca_path| {
+                fs::read(ca_path).context(format!("Failed to read CA certificate: {}", ca_path))
+            })
+            .transpose()?;
+        Ok(TlsFiles { cert, key, ca_cert })
+    }
+
+    /// Hash of the file contents, which changes when a certificate is rotated
+    fn digest(&self) -> String {
+        let mut hasher = Sha256::new();
+        for part in [Some(&self.cert), Some(&self.key), self.ca_cert.as_ref()] {
+            let part = part.map(Vec::as_slice).unwrap_or_default();
+            hasher.update((part.len() as u64).to_le_bytes());
+            hasher.update(part);
+        }
+        format!("{:x}", hasher.finalize())
+    }
+}
+
+const REDACTED: &str = "<redacted>";
+
+/// Per-source request options for a URL prompt, resource or external config
+#[derive(Clone, Copy, Default)]
+pub struct HttpSource<'a> {
+    pub headers: Option<&'a HashMap<String, String>>,
+    pub auth: Option<&'a HttpAuth>,
+    pub tls: Option<&'a TlsConfig>,
+}
+
+impl fmt::Debug for HttpSource<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let headers = self
+            .headers
+            .map(|headers| {
+                headers
+                    .keys()
+                    .map(|name| (name.as_str(), REDACTED))
+                    .collect::<HashMap<_, _>>()
+            })
+            .unwrap_or_default();
+        f.debug_struct("HttpSource")
+            .field("headers", &headers)
+            .field("auth", &self.auth)
+            .field("tls", &self.tls)
+            .finish()
+    }
+}
+
+impl HttpSource<'_> {
+    pub fn is_anonymous(&self) -> bool {
+        self.headers.is_none_or(|headers| headers.is_empty())
+            && self.auth.is_none()
+            && self.tls.is_none()
+    }
+
+    /// Hash of the headers, credentials and client certificate, so that
+    /// sources fetching the same URL differently get separate cache entries.
+    /// `tls_digest` is the hash of the TLS file contents. Empty for anonymous
+    /// sources
+    fn fingerprint(&self, tls_digest: Option<&str>) -> String {
+        if self.is_anonymous() {
+            return String::new();
+        }
+        let mut hasher = Sha256::new();
+        let mut headers: Vec<_> = self.headers.into_iter().flatten().collect();
+        headers.sort();
+        for (name, value) in headers {
+            hasher.update(format!("header:{}:{}\n", name.to_ascii_lowercase(), value));
+        }
+        match self.auth {
+            Some(HttpAuth::Basic { username, password }) => hasher.update(format!(
+                "basic:{}:{}\n",
+                username,
+                password.as_deref().unwrap_or_default()
+            )),
+            Some(HttpAuth::Bearer { token }) => hasher.update(format!("bearer:{}\n", token)),
+            None => {}
+        }
+        if let Some(tls_digest) = tls_digest {
+            hasher.update(format!("tls:{}\n", tls_digest));
+        }
+        format!("{:x}", hasher.finalize())
+    }
+}
+
+/// Body and caching headers of a successful HTTP response
+pub struct HttpResponse {
+    pub body: Vec<u8>,
+    pub content_type: Option<String>,
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+    pub cache_control: Option<String>,
+}
+
+impl HttpResponse {
+    pub fn text(&self) -> String {
+        String::from_utf8_lossy(&self.body).to_string()
+    }
+}
+
+/// Async HTTP client with timeouts, retries with backoff and a response size cap
+#[derive(Clone)]
+pub struct HttpClient {
+    client: reqwest::Client,
+    /// Clients presenting a client certificate, by TLS file paths, with the
+    /// hash of the file contents they were built from
+    tls_clients: Arc<Mutex<HashMap<String, (String, reqwest::Client)>>>,
+    cache: Option<HttpCache>,
+    config: HttpConfig,
+}
+
+impl HttpClient {
+    pub fn new(config: HttpConfig) -> Result<Self> {
+        let client = Self::builder(&config)?
+            .build()
+            .context("Failed to build HTTP client")?;
+
+        let cache = HttpCache::new(config.cache.clone());
+
+        Ok(HttpClient {
+            client,
+            tls_clients: Arc::default(),
+            cache,
+            config,
+        })
+    }
+
+    fn builder(config: &HttpConfig) -> Result<reqwest::ClientBuilder> {
+        let mut builder = reqwest::Client::builder()
+            .timeout(Duration::from_secs(config.timeout_secs))
+            .connect_timeout(Duration::from_secs(config.connect_timeout_secs));
+
+        if let Some(proxy_url) = &config.proxy {
+            let proxy = reqwest::Proxy::all(proxy_url)
+                .context(format!("Invalid proxy URL: {}", proxy_url))?
+                .no_proxy(
+                    config
+                        .no_proxy
+                        .as_deref()
+                        .and_then(reqwest::NoProxy::from_string),
+                );
+            builder = builder.proxy(proxy);
+        }
+
+        Ok(builder)
+    }
+
+    /// The client presenting the client certificate of a source, built on
+    /// first use and reused until the certificate files change
+    fn tls_client(
+        &self,
+        tls: &TlsConfig,
+        files: &TlsFiles,
+        digest: &str,
+    ) -> Result<reqwest::Client> {
+        let paths = format!(
+            "{}\n{}\n{}",
+            tls.client_cert,
+            tls.client_key,
+            tls.ca_cert.as_deref().unwrap_or_default()
+        );
+        let mut clients = self.tls_clients.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some((built_from, client)) = clients.get(&paths) {
+            if built_from == digest {
+                return Ok(client.clone());
+            }
+        }
+
+        let identity = reqwest::Identity::from_pkcs8_pem(&files.cert, &files.key)
+            .context(format!("Invalid client certificate: {}", tls.client_cert))?;
+        let mut builder = Self::builder(&self.config)?.identity(identity);
+        if let (Some(ca_path), Some(ca)) = (&tls.ca_cert, &files.ca_cert) {
+            let ca = reqwest::Certificate::from_pem(ca)
+                .context(format!("Invalid CA certificate: {}", ca_path))?;
+            builder = builder.add_root_certificate(ca);
+        }
+
+        let client = builder.build().context("Failed to build HTTP client")?;
+        clients.insert(paths, (digest.to_string(), client.clone()));
+        Ok(client)
+    }
+
+    /// Build a GET request with the headers and credentials of a source
+    fn request(
+        &self,
+        client: &reqwest::Client,
+        url: &str,
+        source: &HttpSource<'_>,
+    ) -> reqwest::RequestBuilder {
+        let mut request = client.get(url);
+
+        if let Some(headers) = source.headers {
+            for (name, value) in headers {
+                request = request.header(name.as_str(), value.as_str());
+            }
+        }
+
+        match source.auth {
+            Some(HttpAuth::Basic { username, password }) => {
+                request = request.basic_auth(username, password.as_ref());
+            }
+            Some(HttpAuth::Bearer { token }) => {
+                request = request.bearer_auth(token);
+            }
+            None => {}
+        }
+
+        request
+    }
+
+    /// Fetch a URL through the cache, revalidating stale entries and falling
+    /// back to them in offline mode when the network fails
+    pub async fn fetch(&self, url: &str, source: &HttpSource<'_>) -> Result<HttpResponse> {
+        // The files are read on every fetch so that rotated certificates are
+        // picked up, the client is only rebuilt when they change
+        let (tls_client, tls_digest) = match source.tls {
+            Some(tls) => {
+                let files = TlsFiles::read(tls)?;
+                let digest = files.digest();
+                (Some(self.tls_client(tls, &files, &digest)?), Some(digest))
+            }
+            None => (None, None),
+        };
+        let client = tls_client.as_ref().unwrap_or(&self.client);
+        let request = self.request(client, url, source);
+
+        // Responses fetched with credentials are only cached on request
+        let cache = self
+            .cache
+            .as_ref()
+            .filter(|cache| source.is_anonymous() || cache.credentialed());
+        let Some(cache) = cache else {
+            return match self.fetch_with_retries(&request, url, None).await? {
+                FetchOutcome::Modified(response) => Ok(response),
+                FetchOutcome::NotModified(_) => Err(anyhow::anyhow!(
+                    "Unexpected 304 Not Modified when fetching URL: {}",
+                    url
+                )),
+            };
+        };
+
+        let key = HttpCache::key(url, &source.fingerprint(tls_digest.as_deref()));
+        let mut cached = cache.get(&key, url);
+        if let Some(entry) = cached.take() {
+            if cache.is_fresh(&entry) {
+                return Ok(entry.into_response());
+            }
+            cached = Some(entry);
+        }
+
+        match self
+            .fetch_with_retries(&request, url, cached.as_ref().map(|entry| &entry.metadata))
+            .await
+        {
+            Ok(FetchOutcome::Modified(response)) => {
+                // Caching is best effort, a failing cache must not fail the request
+                let _ = cache.store(&key, url, &response);
+                Ok(response)
+            }
+            Ok(FetchOutcome::NotModified(cache_control)) => match cached {
+                Some(mut entry) => {
+                    let _ = cache.refresh(&key, &mut entry, cache_control.as_deref());
+                    Ok(entry.into_response())
+                }
+                None => Err(anyhow::anyhow!(
+                    "Unexpected 304 Not Modified when fetching URL: {}",
+                    url
+                )),
+            },
+            Err(e) => match cached {
+                Some(entry) if cache.offline() => Ok(entry.into_response()),
+                _ => Err(e),
+            },
+        }
+    }
+
+    /// Fetch a URL, retrying network errors and 429/5xx responses
+    async fn fetch_with_retries(
+        &self,
+        request: &reqwest::RequestBuilder,
+        url: &str,
+        cached: Option<&CacheMetadata>,
+    ) -> Result<FetchOutcome> {
+        let mut attempt = 0;
+        loop {
+            let request = request
+                .try_clone()
+                .context(format!("Failed to build request for URL: {}", url))?;
+            match self.fetch_once(request, url, cached).await {
+                Ok(outcome) => return Ok(outcome),
+                Err(FetchError::Fatal(e)) => return Err(e),
+                Err(FetchError::Retryable(e)) => {
+                    if attempt >= self.config.retries {
+                        return Err(e);
+                    }
+                    let backoff = 2u64
+                        .checked_pow(attempt)
+                        .map_or(u64::MAX, |factor| {
+                            self.config.retry_backoff_ms.saturating_mul(factor)
+                        })
+                        .min(MAX_RETRY_BACKOFF_MS);
+                    tokio::time::sleep(Duration::from_millis(backoff)).await;
+                    attempt += 1;
+                }
+            }
+        }
+    }
+
+    /// Fetch a URL and return its body as text
+    pub async fn fetch_text(&self, url: &str, source: &HttpSource<'_>) -> Result<String> {
+        Ok(self.fetch(url, source).await?.text())
+    }
+
+    async fn fetch_once(
+        &self,
+        mut request: reqwest::RequestBuilder,
+        url: &str,
+        cached: Option<&CacheMetadata>,
+    ) -> std::result::Result<FetchOutcome, FetchError> {
+        if let Some(cached) = cached {
+            if let Some(etag) = &cached.etag {
+                request = request.header(reqwest::header::IF_NONE_MATCH, etag);
+            }
+            if let Some(last_modified) = &cached.last_modified {
+                request = request.header(reqwest::header::IF_MODIFIED_SINCE, last_modified);
+            }
+        }
+
+        let mut response = request.send().await.map_err(|e| {
+            FetchError::Retryable(anyhow::anyhow!("Failed to fetch URL '{}': {}", url, e))
+        })?;
+
+        let header = |name: reqwest::header::HeaderName| {
+            response
+                .headers()
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(|value| value.to_string())
+        };
+        let content_type = header(reqwest::header::CONTENT_TYPE);
+        let etag = header(reqwest::header::ETAG);
+        let last_modified = header(reqwest::header::LAST_MODIFIED);
+        let cache_control = header(reqwest::header::CACHE_CONTROL);
+
+        let status = response.status();
+        if status == reqwest::StatusCode::NOT_MODIFIED && cached.is_some() {
+            return Ok(FetchOutcome::NotModified(cache_control));
+        }
+
+        if !status.is_success() {
+            let error = anyhow::anyhow!("HTTP error {} when fetching URL: {}", status, url);
+            if status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+                return Err(FetchError::Retryable(error));
+            }
+            return Err(FetchError::Fatal(error));
+        }
+
+        let max_size = self.config.max_size_bytes;
+        if response.content_length().is_some_and(|len| len > max_size) {
+            return Err(FetchError::Fatal(anyhow::anyhow!(
+                "Response from URL '{}' exceeds the maximum size of {} bytes",
+                url,
+                max_size
+            )));
+        }
+
+        let mut body = Vec::new();
+        while let Some(chunk) = response.chunk().await.map_err(|e| {
+            FetchError::Retryable(anyhow::anyhow!(
+                "Failed to read response body from URL '{}': {}",
+                url,
+                e
+            ))
+        })? {
+            body.extend_from_slice(&chunk);
+            if body.len() as u64 > max_size {
+                return Err(FetchError::Fatal(anyhow::anyhow!(
+                    "Response from URL '{}' exceeds the maximum size of {} bytes",
+                    url,
+                    max_size
+                )));
+            }
+        }
+
+        Ok(FetchOutcome::Modified(HttpResponse {
+            body,
+            content_type,
+            etag,
+            last_modified,
+            cache_control,
+        }))
+    }
+}
+
+enum FetchOutcome {
+    Modified(HttpResponse),
+    NotModified(Option<String>),
+}
+
+enum FetchError {
+    Retryable(anyhow::Error),
+    Fatal(anyhow::Error),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fingerprint_depends_on_tls_file_contents() {
+        let dir = tempfile::tempdir().unwrap();
+        let cert = dir.path().join("client.pem");
+        let key = dir.path().join("client.key");
+        fs::write(&key, "key").unwrap();
+        let tls = TlsConfig {
+            client_cert: cert.to_string_lossy().to_string(),
+            client_key: key.to_string_lossy().to_string(),
+            ca_cert: None,
+        };
+        let source = HttpSource {
+            tls: Some(&tls),
+            ..HttpSource::default()
+        };
+        let fingerprint = || {
+            let digest = TlsFiles::read(&tls).unwrap().digest();
+            source.fingerprint(Some(&digest))
+        };
+
+        fs::write(&cert, "first").unwrap();
+        let first = fingerprint();
+        assert_eq!(first, fingerprint());
+        fs::write(&cert, "rotated").unwrap();
+        assert_ne!(first, fingerprint());
+        assert_eq!(HttpSource::default().fingerprint(None), "");
+    }
+}
@@ -0,0 +1,165 @@
+use anyhow::{Context, Result};
+use serde_yaml::Value;
+use std::env;
+
+/// Expand `${VAR}` and `${VAR:-default}` references with the values of
+/// environment variables. `$$` produces a literal `$`.
+pub fn expand_env(value: &str) -> Result<String> {
+    let mut expanded = String::with_capacity(value.len());
+    let mut rest = value;
+
+    while let Some(start) = rest.find('$') {
+        expanded.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+
+        if let Some(after) = after.strip_prefix('$') {
+            expanded.push('$');
+            rest = after;
+            continue;
+        }
+
+        let Some(after) = after.strip_prefix('{') else {
+            expanded.push('$');
+            rest = after;
+            continue;
+        };
+
+        let end = after
+            .find('}')
+            .ok_or_else(|| anyhow::anyhow!("Unterminated variable reference in: {}", value))?;
+        let reference = &after[..end];
+        let (name, default) = match reference.split_once(":-") {
+            Some((name, default)) => (name, Some(default)),
+            None => (reference, None),
+        };
+
+        // Like the shell, the default also applies to variables set to an empty value
+        let resolved = match (env::var(name), default) {
+            (Ok(resolved), Some(default)) if resolved.is_empty() => default.to_string(),
+            (Ok(resolved), _) => resolved,
+            (Err(_), Some(default)) => default.to_string(),
+            (Err(_), None) => {
+                return Err(anyhow::anyhow!(
+                    "Environment variable '{}' is not set and has no default",
+                    name
+                ))
+            }
+        };
+        expanded.push_str(&resolved);
+        rest = &after[end + 1..];
+    }
+
+    expanded.push_str(rest);
+    Ok(expanded)
+}
+
+/// Expand a leading `~` to the home directory of the current user
+pub fn expand_home(value: &str) -> String {
+    if value == "~" || value.starts_with("~/") {
+        if let Some(home) = dirs::home_dir() {
+            return format!("{}{}", home.to_string_lossy(), &value[1..]);
+        }
+    }
+    value.to_string()
+}
+
+/// Keys whose values are passed through verbatim, so that inline scripts can
+/// use their own `${VAR}` syntax
+const VERBATIM_KEYS: &[&str] = &["script"];
+
+/// Expand environment variables and `~` in every string of a parsed YAML document
+pub fn interpolate_yaml(value: &mut Value, location: &str) -> Result<()> {
+    match value {
+        Value::String(text) => {
+            let expanded = expand_env(text).context(format!("Invalid value at {}", location))?;
+            *text = expand_home(&expanded);
+        }
+        Value::Sequence(items) => {
+            for (index, item) in items.iter_mut().enumerate() {
+                interpolate_yaml(item, &format!("{location}[{index}]"))?;
+            }
+        }
+        Value::Mapping(mapping) => {
+            for (key, item) in mapping.iter_mut() {
+                let key = key.as_str().unwrap_or("?");
+                if VERBATIM_KEYS.contains(&key) {
+                    continue;
+                }
+                let location = if location.is_empty() {
+                    key.to_string()
+                } else {
+                    format!("{location}.{key}")
+                };
+                interpolate_yaml(item, &location)?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expands_variables_and_defaults() {
+        env::set_var("MYCOMMANDMCP_TEST_SET", "value");
+        env::set_var("MYCOMMANDMCP_TEST_EMPTY", "");
+        env::remove_var("MYCOMMANDMCP_TEST_UNSET");
+
+        assert_eq!(
+            expand_env("a ${MYCOMMANDMCP_TEST_SET} b").unwrap(),
+            "a value b"
+        );
+        assert_eq!(
+            expand_env("${MYCOMMANDMCP_TEST_UNSET:-fallback}").unwrap(),
+            "fallback"
+        );
+        assert_eq!(
+            expand_env("${MYCOMMANDMCP_TEST_EMPTY:-fallback}").unwrap(),
+            "fallback"
+        );
+        assert_eq!(
+            expand_env("${MYCOMMANDMCP_TEST_SET:-fallback}").unwrap(),
+            "value"
+        );
+    }
+
+    #[test]
+    fn keeps_literal_dollars() {
+        assert_eq!(
+            expand_env("cost $$5 and $HOME").unwrap(),
+            "cost $5 and $HOME"
+        );
+        assert_eq!(expand_env("ends with $").unwrap(), "ends with $");
+    }
+
+    #[test]
+    fn rejects_unset_and_unterminated_references() {
+        env::remove_var("MYCOMMANDMCP_TEST_MISSING");
+        let error = expand_env("${MYCOMMANDMCP_TEST_MISSING}").unwrap_err();
+        assert!(error.to_string().contains("MYCOMMANDMCP_TEST_MISSING"));
+        assert!(expand_env("${UNTERMINATED").is_err());
+    }
+
+    #[test]
+    fn expands_home_only_at_the_start() {
+        let home = dirs::home_dir().unwrap().to_string_lossy().to_string();
+        assert_eq!(expand_home("~"), home);
+        assert_eq!(expand_home("~/notes"), format!("{home}/notes"));
+        assert_eq!(expand_home("~other/notes"), "~other/notes");
+        assert_eq!(expand_home("a/~/b"), "a/~/b");
+    }
+
+    #[test]
+    fn leaves_scripts_verbatim() {
+        env::set_var("MYCOMMANDMCP_TEST_TOOL", "tool");
+        let mut value: Value =
+            serde_yaml::from_str("name: ${MYCOMMANDMCP_TEST_TOOL}\nscript: echo ${HOME}\n")
+                .unwrap();
+        interpolate_yaml(&mut value, "").unwrap();
+        assert_eq!(value["name"].as_str(), Some("tool"));
+        assert_eq!(value["script"].as_str(), Some("echo ${HOME}"));
+    }
+}
@@ -1,21 +1,48 @@
 mod cli_parser;
+mod conditions;
+mod filters;
+mod http_cache;
+mod http_client;
+mod interpolate;
 mod logging;
 mod mcp_server;
+mod mime_sniff;
+mod output_files;
+mod output_format;
+mod scratch;
+mod validate;
 
 use anyhow::{Context, Result};
 use clap::Parser;
 use serde_json::json;
 use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
 
-use cli_parser::{find_config_file, load_config, Args};
+use cli_parser::{config_schema, find_config_file, load_config, Args, Commands};
 use mcp_server::MyCommandMCPServer;
 
 #[tokio::main]
 async fn main() -> Result<()> {
     let args = Args::parse();
 
+    if let Some(Commands::Schema) = args.command {
+        println!("{}", serde_json::to_string_pretty(&config_schema())?);
+        return Ok(());
+    }
+
     let config_path = find_config_file(args.config)?;
-    let config = load_config(&config_path)?;
+
+    if let Some(Commands::Validate { json, strict }) = args.command {
+        let report = validate::validate_config(&config_path, args.profile.as_deref()).await;
+        if json {
+            println!("{}", serde_json::to_string_pretty(&report)?);
+        } else {
+            report.print();
+        }
+        std::process::exit(if report.failed(strict) { 1 } else { 0 });
+    }
+
+    let mut config = load_config(&config_path, args.profile.as_deref()).await?;
+    let skipped = std::mem::take(&mut config.skipped);
 
     // Initialize logger
     let logger = logging::DualLogger::new(args.log_file.as_deref())
@@ -25,6 +52,15 @@ async fn main() -> Result<()> {
 
     server.log("MyCommandMCP Server starting...")?;
     server.log(&format!("Config file: {config_path}"))?;
+    if let Some(profile) = &args.profile {
+        server.log(&format!("Profile: {profile}"))?;
+    }
+    for warning in &server.warnings {
+        server.log(&format!("Warning: {warning}"))?;
+    }
+    for skipped in &skipped {
+        server.log(&format!("Skipped {skipped}"))?;
+    }
     server.log(&format!(
         "Loaded {} tools, {} prompts, and {} resources:",
         server.tools.len(),
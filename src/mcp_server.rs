@@ -1,12 +1,22 @@
 use anyhow::{Context, Result};
 use base64::Engine;
 use serde::Serialize;
-use serde_json::json;
+use serde_json::{json, Value};
 use std::collections::HashMap;
-use std::process::Command;
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitStatus, Stdio};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
 
-use crate::cli_parser::{ConfigData, PromptConfig, ResourceConfig, ToolConfig};
+use crate::cli_parser::{
+    evaluate_condition, is_url, render_template, ConfigData, FailurePolicy, Presentation,
+    PromptConfig, ResourceConfig, ToolConfig,
+};
+use crate::http_client::HttpClient;
 use crate::logging::DualLogger;
+use crate::mime_sniff::sniff_mime;
+use crate::output_files::{OutputFile, OutputFiles};
+use crate::scratch::create_scratch_dir;
 
 #[derive(Debug, Serialize)]
 pub struct CommandResult {
@@ -16,12 +26,60 @@ pub struct CommandResult {
     pub content_type: Option<String>,
     pub content_disposition: Option<String>,
     pub is_binary: bool,
+    /// Meaning of the exit code, from the tool's `exit_codes`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exit_meaning: Option<String>,
+    /// Whether the call failed, according to the tool's `success_codes` and `stderr_is_error`
+    #[serde(skip)]
+    pub is_error: bool,
+    /// Signal that terminated the command, on Unix
+    #[serde(skip)]
+    pub signal: Option<i32>,
+    /// Files written by the command, served as temporary resources
+    #[serde(skip)]
+    pub output_files: Vec<OutputFile>,
+    /// Working directory kept after a failed call for debugging
+    #[serde(skip)]
+    pub kept_dir: Option<PathBuf>,
+    /// Whether the output was cut to the tool's `max_output_bytes`
+    #[serde(skip)]
+    pub truncated: bool,
+    #[serde(skip)]
+    pub duration_ms: u64,
+    /// Exit code of each step of a pipeline tool
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub steps: Option<Vec<StepResult>>,
 }
 
+#[derive(Debug, Serialize)]
+pub struct StepResult {
+    /// Name of a workflow step
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    pub command: String,
+    /// Exit code of the step, `None` when its `if` condition skipped it
+    pub status_code: Option<i32>,
+    pub error: String,
+}
+
+/// MCP protocol revisions the server speaks, oldest first
+const PROTOCOL_VERSIONS: &[&str] = &["2024-11-05", "2025-03-26", "2025-06-18"];
+
+/// First revision with `audio` content
+const AUDIO_CONTENT_VERSION: &str = "2025-03-26";
+
+/// First revision with `resource_link` content, `structuredContent` and `outputSchema`
+const STRUCTURED_OUTPUT_VERSION: &str = "2025-06-18";
+
 pub struct MyCommandMCPServer {
     pub tools: HashMap<String, ToolConfig>,
     pub prompts: HashMap<String, PromptConfig>,
     pub resources: HashMap<String, ResourceConfig>,
+    pub warnings: Vec<String>,
+    http: HttpClient,
+    output_files: OutputFiles,
+    /// Protocol revision agreed on in `initialize`
+    protocol_version: Mutex<&'static str>,
     logger: DualLogger,
 }
 
@@ -31,10 +89,38 @@ impl MyCommandMCPServer {
             tools: config.tools,
             prompts: config.prompts,
             resources: config.resources,
+            warnings: config.warnings,
+            http: config.http,
+            output_files: OutputFiles::default(),
+            protocol_version: Mutex::new(PROTOCOL_VERSIONS[PROTOCOL_VERSIONS.len() - 1]),
             logger,
         }
     }
 
+    /// Agree on the revision requested by the client when it is supported,
+    /// on the latest one otherwise
+    fn negotiate_protocol_version(&self, requested: Option<&str>) -> &'static str {
+        let version = PROTOCOL_VERSIONS
+            .iter()
+            .find(|version| Some(**version) == requested)
+            .unwrap_or(&PROTOCOL_VERSIONS[PROTOCOL_VERSIONS.len() - 1]);
+        *self
+            .protocol_version
+            .lock()
+            .unwrap_or_else(|e| e.into_inner()) = version;
+        version
+    }
+
+    /// Whether the agreed revision is `version` or newer. Revisions are dates,
+    /// so they compare as strings
+    fn supports(&self, version: &str) -> bool {
+        *self
+            .protocol_version
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            >= version
+    }
+
     pub fn log(&self, message: &str) -> Result<()> {
         self.logger.log(message).context("Failed to write log")
     }
@@ -50,8 +136,105 @@ impl MyCommandMCPServer {
             .get(tool_name)
             .context(format!("Tool '{tool_name}' not found"))?;
 
-        let mut cmd = Command::new(&tool.command);
-        cmd.current_dir(&tool.path);
+        let started = Instant::now();
+        let mut result = if !tool.workflow.is_empty() {
+            self.execute_workflow(tool, args, input).await?
+        } else {
+            self.run_tool(tool, args, input).await?
+        };
+        result.duration_ms = started.elapsed().as_millis() as u64;
+        Ok(result)
+    }
+
+    /// Run a command, inline script or pipeline tool
+    async fn run_tool(
+        &self,
+        tool: &ToolConfig,
+        args: Option<&str>,
+        input: Option<&str>,
+    ) -> Result<CommandResult> {
+        let working_dir = self.create_working_dir(tool)?;
+        let cwd = match &working_dir {
+            Some(dir) => dir.path().to_path_buf(),
+            None => PathBuf::from(&tool.path),
+        };
+
+        let mut result = if tool.pipeline.is_empty() {
+            self.execute_single(tool, &cwd, args, input).await?
+        } else {
+            self.execute_pipeline(tool, &cwd, args, input).await?
+        };
+
+        if let Some(dir) = working_dir {
+            self.finish_working_dir(tool, dir, &mut result)?;
+        }
+        Ok(result)
+    }
+
+    /// Create the per-call working directory of a tool with `scratch` or
+    /// `output_files`, populated from the scratch template
+    fn create_working_dir(&self, tool: &ToolConfig) -> Result<Option<tempfile::TempDir>> {
+        if tool.scratch.is_none() && tool.output_files.is_empty() {
+            return Ok(None);
+        }
+        let dir = create_scratch_dir()?;
+        if let Some(scratch) = &tool.scratch {
+            scratch.populate(dir.path(), &tool.path)?;
+        }
+        Ok(Some(dir))
+    }
+
+    /// Register the output files of a call and remove its working directory,
+    /// or keep the directory of a failed call when the tool asks for it
+    fn finish_working_dir(
+        &self,
+        tool: &ToolConfig,
+        dir: tempfile::TempDir,
+        result: &mut CommandResult,
+    ) -> Result<()> {
+        let keep = tool
+            .scratch
+            .as_ref()
+            .is_some_and(|scratch| scratch.keep_on_failure);
+        if result.is_error && keep {
+            let kept = dir.keep();
+            self.log(&format!(
+                "Kept working directory of failed call to '{}': {}",
+                tool.name,
+                kept.display()
+            ))?;
+            result.kept_dir = Some(kept);
+        } else if !tool.output_files.is_empty() {
+            result.output_files = self.output_files.register(
+                &tool.name,
+                dir,
+                &tool.output_files,
+                Duration::from_secs(tool.output_ttl_secs),
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Run a command or inline script tool
+    async fn execute_single(
+        &self,
+        tool: &ToolConfig,
+        cwd: &Path,
+        args: Option<&str>,
+        input: Option<&str>,
+    ) -> Result<CommandResult> {
+        // Inline scripts are written to a private temp file that is removed
+        // once the command has finished
+        let script_path = match &tool.script {
+            Some(script) => Some(write_script(script)?),
+            None => None,
+        };
+
+        let mut cmd = Command::new(tool.program());
+        cmd.current_dir(cwd);
+        if let Some(script_path) = &script_path {
+            cmd.arg(script_path);
+        }
 
         if tool.accepts_args {
             // First add default args if they exist
@@ -70,11 +253,320 @@ impl MyCommandMCPServer {
             }
         }
 
+        let stdin = if tool.accept_input {
+            Some(input.unwrap_or_default().as_bytes())
+        } else {
+            None
+        };
+        let output = self.run_process(cmd, tool.program(), stdin).await?;
+
+        let stderr = String::from_utf8_lossy(&output.stderr).to_string();
+        let status_code = output.status.code().unwrap_or(-1);
+        let signal = exit_signal(&output.status);
+
+        self.command_result(tool, status_code, signal, &output.stdout, stderr, None)
+    }
+
+    /// Run the steps of a pipeline tool, all at once with the stdout of each
+    /// step connected to the stdin of the next, like a shell pipeline
+    async fn execute_pipeline(
+        &self,
+        tool: &ToolConfig,
+        cwd: &Path,
+        args: Option<&str>,
+        input: Option<&str>,
+    ) -> Result<CommandResult> {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let mut children = Vec::new();
+        let mut upstream: Option<Stdio> = None;
+        for (index, step) in tool.pipeline.iter().enumerate() {
+            let mut cmd = Command::new(&step.command);
+            cmd.current_dir(cwd);
+            if let Some(step_args) = &step.args {
+                cmd.args(step_args.split_whitespace());
+            }
+            if tool.accepts_args && step.accepts_args {
+                if let Some(args_str) = args {
+                    cmd.args(args_str.split_whitespace());
+                }
+            }
+
+            let mut tokio_cmd = tokio::process::Command::from(cmd);
+            match upstream.take() {
+                Some(stdio) => {
+                    tokio_cmd.stdin(stdio);
+                }
+                None if tool.accept_input => {
+                    tokio_cmd.stdin(Stdio::piped());
+                }
+                None => {}
+            }
+            tokio_cmd.stdout(Stdio::piped());
+            tokio_cmd.stderr(Stdio::piped());
+            // Steps already started are killed when a later one cannot be spawned
+            tokio_cmd.kill_on_drop(true);
+
+            let mut child = match tokio_cmd.spawn() {
+                Ok(child) => child,
+                Err(e) => {
+                    self.log(&format!("Failed to spawn command {}: {e}", step.command))?;
+                    return Err(e.into());
+                }
+            };
+            if index + 1 < tool.pipeline.len() {
+                let stdout = child
+                    .stdout
+                    .take()
+                    .context("Missing stdout of pipeline step")?;
+                upstream = Some(stdout.try_into()?);
+            }
+            children.push(child);
+        }
+
+        // Feed the input, and drain stdout and every stderr, on separate tasks
+        // so that no step blocks on a full pipe
+        let writer = match (tool.accept_input, children[0].stdin.take()) {
+            (true, Some(mut child_stdin)) => {
+                let input = input.unwrap_or_default().as_bytes().to_vec();
+                Some(tokio::spawn(async move {
+                    // The first step may exit without reading all of its input
+                    let _ = child_stdin.write_all(&input).await;
+                }))
+            }
+            _ => None,
+        };
+        let mut last_stdout = children
+            .last_mut()
+            .and_then(|child| child.stdout.take())
+            .context("Missing stdout of pipeline step")?;
+        let stdout_reader = tokio::spawn(async move {
+            let mut stdout = Vec::new();
+            last_stdout.read_to_end(&mut stdout).await.map(|_| stdout)
+        });
+        let stderr_readers: Vec<_> = children
+            .iter_mut()
+            .map(|child| {
+                let stderr = child.stderr.take();
+                tokio::spawn(async move {
+                    let mut error = Vec::new();
+                    if let Some(mut stderr) = stderr {
+                        let _ = stderr.read_to_end(&mut error).await;
+                    }
+                    error
+                })
+            })
+            .collect();
+
+        let mut statuses = Vec::new();
+        for (child, step) in children.iter_mut().zip(&tool.pipeline) {
+            let status = match child.wait().await {
+                Ok(status) => status,
+                Err(e) => {
+                    self.log(&format!("Failed to execute command {}: {e}", step.command))?;
+                    return Err(e.into());
+                }
+            };
+            self.log(&format!(
+                "Command '{}' completed with status: {}",
+                step.command, status
+            ))?;
+            statuses.push(status);
+        }
+        if let Some(writer) = writer {
+            let _ = writer.await;
+        }
+        let stdout = stdout_reader.await??;
+
+        let mut errors = Vec::new();
+        let mut steps = Vec::new();
+        for ((reader, step), status) in stderr_readers
+            .into_iter()
+            .zip(&tool.pipeline)
+            .zip(&statuses)
+        {
+            let step_error = String::from_utf8_lossy(&reader.await?).to_string();
+            if !step_error.is_empty() {
+                errors.push(step_error.clone());
+            }
+            steps.push(StepResult {
+                name: None,
+                command: step.command.clone(),
+                status_code: Some(status.code().unwrap_or(-1)),
+                error: step_error,
+            });
+        }
+
+        // With pipefail the last failing step decides the status, as with
+        // `set -o pipefail`. Earlier steps killed by SIGPIPE because a later
+        // step stopped reading, as in `yes | head`, do not count as failures
+        let last = statuses.len() - 1;
+        let failed = statuses.iter().enumerate().rposition(|(index, status)| {
+            !status.success() && (index == last || exit_signal(status) != Some(SIGPIPE))
+        });
+        let status = match (tool.on_failure, failed) {
+            (FailurePolicy::Pipefail, Some(index)) => statuses[index],
+            _ => statuses[last],
+        };
+
+        self.command_result(
+            tool,
+            status.code().unwrap_or(-1),
+            exit_signal(&status),
+            &stdout,
+            errors.join(""),
+            Some(steps),
+        )
+    }
+
+    /// Run the named steps of a workflow tool in order, binding the result of
+    /// each step to template variables for the later steps
+    async fn execute_workflow(
+        &self,
+        tool: &ToolConfig,
+        args: Option<&str>,
+        input: Option<&str>,
+    ) -> Result<CommandResult> {
+        // Arguments and input only reach the steps when the workflow accepts them
+        let args = args.filter(|_| tool.accepts_args).unwrap_or_default();
+        let input = input.filter(|_| tool.accept_input).unwrap_or_default();
+        let mut variables = HashMap::new();
+        variables.insert("args".to_string(), args.to_string());
+        variables.insert("input".to_string(), input.to_string());
+
+        // Command steps share the workflow's scratch directory
+        let working_dir = self.create_working_dir(tool)?;
+        let cwd = match &working_dir {
+            Some(dir) => dir.path().to_path_buf(),
+            None => PathBuf::from(&tool.path),
+        };
+
+        let mut steps = Vec::new();
+        let mut errors = Vec::new();
+        let mut status_code = 0;
+        let mut signal = None;
+        let mut failed = false;
+        let mut output = String::new();
+
+        for step in &tool.workflow {
+            let target = step
+                .tool
+                .as_deref()
+                .or(step.command.as_deref())
+                .unwrap_or_default()
+                .to_string();
+
+            if let Some(condition) = &step.condition {
+                if !evaluate_condition(condition, &variables) {
+                    steps.push(StepResult {
+                        name: Some(step.name.clone()),
+                        command: target,
+                        status_code: None,
+                        error: String::new(),
+                    });
+                    continue;
+                }
+            }
+
+            let step_args = step.args.as_ref().map(|a| render_template(a, &variables));
+            let step_input = step.input.as_ref().map(|i| render_template(i, &variables));
+
+            let result = match &step.tool {
+                Some(tool_name) => {
+                    let called = self
+                        .tools
+                        .get(tool_name)
+                        .context(format!("Tool '{tool_name}' not found"))?;
+                    self.run_tool(called, step_args.as_deref(), step_input.as_deref())
+                        .await?
+                }
+                None => {
+                    let mut cmd = Command::new(&target);
+                    cmd.current_dir(&cwd);
+                    if let Some(step_args) = &step_args {
+                        cmd.args(step_args.split_whitespace());
+                    }
+                    let stdin = step_input.as_ref().map(|i| i.as_bytes());
+                    let process = self.run_process(cmd, &target, stdin).await?;
+                    let status_code = process.status.code().unwrap_or(-1);
+                    CommandResult {
+                        signal: exit_signal(&process.status),
+                        output_files: Vec::new(),
+                        kept_dir: None,
+                        truncated: false,
+                        duration_ms: 0,
+                        status_code,
+                        output: String::from_utf8_lossy(&process.stdout).to_string(),
+                        error: String::from_utf8_lossy(&process.stderr).to_string(),
+                        content_type: None,
+                        content_disposition: None,
+                        is_binary: false,
+                        exit_meaning: None,
+                        is_error: status_code != 0,
+                        steps: None,
+                    }
+                }
+            };
+
+            let prefix = format!("steps.{}", step.name);
+            variables.insert(format!("{prefix}.output"), result.output.clone());
+            variables.insert(format!("{prefix}.error"), result.error.clone());
+            variables.insert(
+                format!("{prefix}.status_code"),
+                result.status_code.to_string(),
+            );
+            if let Ok(parsed) = serde_json::from_str::<Value>(&result.output) {
+                bind_json(&mut variables, &format!("{prefix}.json"), &parsed);
+            }
+
+            if !result.error.is_empty() {
+                errors.push(result.error.clone());
+            }
+            steps.push(StepResult {
+                name: Some(step.name.clone()),
+                command: target,
+                status_code: Some(result.status_code),
+                error: result.error,
+            });
+
+            status_code = result.status_code;
+            signal = result.signal;
+            failed = result.is_error;
+            output = result.output;
+            if failed && !step.continue_on_error {
+                break;
+            }
+        }
+
+        // Steps decide success with their own exit code rules
+        let mut result = self.command_result(
+            tool,
+            status_code,
+            signal,
+            output.as_bytes(),
+            errors.join(""),
+            Some(steps),
+        )?;
+        result.is_error = failed || (tool.stderr_is_error && !result.error.trim().is_empty());
+
+        if let Some(dir) = working_dir {
+            self.finish_working_dir(tool, dir, &mut result)?;
+        }
+        Ok(result)
+    }
+
+    /// Spawn a command, write `stdin` to it if given, and wait for its output
+    async fn run_process(
+        &self,
+        cmd: Command,
+        program: &str,
+        stdin: Option<&[u8]>,
+    ) -> Result<std::process::Output> {
         // Create tokio process command
         let mut tokio_cmd = tokio::process::Command::from(cmd);
 
         // Configure stdin if the tool accepts input
-        if tool.accept_input {
+        if stdin.is_some() {
             tokio_cmd.stdin(std::process::Stdio::piped());
         }
 
@@ -85,72 +577,319 @@ impl MyCommandMCPServer {
         let mut child = match tokio_cmd.spawn() {
             Ok(child) => child,
             Err(e) => {
-                self.log(&format!("Failed to spawn command {}: {}", tool.command, e))?;
+                self.log(&format!("Failed to spawn command {program}: {e}"))?;
                 return Err(e.into());
             }
         };
 
-        // If tool accepts input and input is provided, write to stdin
-        if tool.accept_input && input.is_some() {
-            if let Some(stdin) = child.stdin.as_mut() {
-                if let Some(input_str) = input {
+        // Write the input on a separate task, so that a command producing a lot
+        // of output before reading all of its input cannot deadlock
+        let writer = match (stdin, child.stdin.take()) {
+            (Some(input), Some(mut child_stdin)) => {
+                let input = input.to_vec();
+                Some(tokio::spawn(async move {
                     use tokio::io::AsyncWriteExt;
-                    stdin.write_all(input_str.as_bytes()).await?;
-                    stdin.flush().await?;
-                }
+                    // The command may exit without reading all of its input
+                    let _ = child_stdin.write_all(&input).await;
+                    let _ = child_stdin.flush().await;
+                    // Dropping stdin closes it to signal end of input
+                }))
             }
-            // Close stdin to signal end of input
-            child.stdin.take();
-        }
+            _ => None,
+        };
 
         // Wait for the process to complete and get output
         let output = match child.wait_with_output().await {
             Ok(output) => output,
             Err(e) => {
-                self.log(&format!(
-                    "Failed to execute command {}: {}",
-                    tool.command, e
-                ))?;
+                self.log(&format!("Failed to execute command {program}: {e}"))?;
                 return Err(e.into());
             }
         };
+        if let Some(writer) = writer {
+            let _ = writer.await;
+        }
 
         // Log command execution result
         self.log(&format!(
             "Command '{}' completed with status: {}",
-            tool.command, output.status
+            program, output.status
         ))?;
 
-        let stderr = String::from_utf8_lossy(&output.stderr).to_string();
-        let status_code = output.status.code().unwrap_or(-1);
+        Ok(output)
+    }
+
+    /// Build the result of a tool call from the final output of its command
+    fn command_result(
+        &self,
+        tool: &ToolConfig,
+        status_code: i32,
+        signal: Option<i32>,
+        stdout: &[u8],
+        error: String,
+        steps: Option<Vec<StepResult>>,
+    ) -> Result<CommandResult> {
+        // Without a configured content type, binary output is recognized by
+        // its magic bytes unless it is meant to be parsed
+        let content_type = tool.content_type.clone().or_else(|| {
+            tool.output_format
+                .is_none()
+                .then(|| sniff_mime(stdout))
+                .flatten()
+                .map(|mime| mime.to_string())
+        });
 
         // Determine if output should be treated as binary based on content type
-        let is_binary = tool
-            .content_type
+        let is_binary = content_type
             .as_ref()
             .map(|ct| {
                 !ct.starts_with("text/") && ct != "application/json" && ct != "application/xml"
             })
             .unwrap_or(false);
 
-        let stdout = if is_binary {
-            // For binary content, encode as base64
-            base64::engine::general_purpose::STANDARD.encode(&output.stdout)
+        let mut truncated = false;
+        let output = if is_binary {
+            // Binary output is cut before it is encoded, so that the base64 stays valid
+            let stdout = match tool.max_output_bytes {
+                Some(max_bytes) if stdout.len() > max_bytes => {
+                    truncated = true;
+                    &stdout[..max_bytes]
+                }
+                _ => stdout,
+            };
+            base64::engine::general_purpose::STANDARD.encode(stdout)
         } else {
-            // For text content, convert to string as usual
-            String::from_utf8_lossy(&output.stdout).to_string()
+            // For text content, convert to string and post-process it
+            let mut output = String::from_utf8_lossy(stdout).to_string();
+            for filter in &tool.filters {
+                output = filter.apply(&output).context(format!(
+                    "Failed to apply {} filter of tool '{}'",
+                    filter.name(),
+                    tool.name
+                ))?;
+            }
+            if let Some(max_bytes) = tool.max_output_bytes {
+                if output.len() > max_bytes {
+                    // Cut at a character boundary
+                    let mut end = max_bytes;
+                    while !output.is_char_boundary(end) {
+                        end -= 1;
+                    }
+                    output.truncate(end);
+                    truncated = true;
+                }
+            }
+            output
         };
 
+        let is_error = !tool.success_codes.contains(&status_code)
+            || (tool.stderr_is_error && !error.trim().is_empty());
+
         Ok(CommandResult {
             status_code,
-            output: stdout,
-            error: stderr,
-            content_type: tool.content_type.clone(),
+            output,
+            error,
+            content_type,
             content_disposition: tool.content_disposition.clone(),
             is_binary,
+            exit_meaning: tool.exit_codes.get(&status_code).cloned(),
+            is_error,
+            signal,
+            output_files: Vec::new(),
+            kept_dir: None,
+            truncated,
+            duration_ms: 0,
+            steps,
         })
     }
 
+    /// Content items for the files written by a tool call: `resource_link`
+    /// items, or the embedded files for clients without resource links
+    fn output_file_content(&self, result: &CommandResult) -> Result<Vec<Value>> {
+        let links = self.supports(STRUCTURED_OUTPUT_VERSION);
+        result
+            .output_files
+            .iter()
+            .map(|file| {
+                if links {
+                    return Ok(json!({
+                        "type": "resource_link",
+                        "uri": file.uri,
+                        "name": file.name,
+                        "mimeType": file.mime_type,
+                        "size": file.size
+                    }));
+                }
+                let content = std::fs::read(&file.path).context(format!(
+                    "Failed to read output file '{}'",
+                    file.path.display()
+                ))?;
+                Ok(json!({
+                    "type": "resource",
+                    "resource": resource_contents(&file.uri, &file.mime_type, &content)
+                }))
+            })
+            .collect()
+    }
+
+    /// Read a configured resource and build its MCP resource contents item
+    async fn read_resource(&self, resource_name: &str, resource_uri: &str) -> Result<Value> {
+        // Files written by tool calls are served until they expire
+        if let Some(file) = self.output_files.get(resource_uri) {
+            self.log(&format!("Reading output file: {}", file.path.display()))?;
+            let content = std::fs::read(&file.path).context(format!(
+                "Failed to read output file '{}'",
+                file.path.display()
+            ))?;
+            return Ok(resource_contents(resource_uri, &file.mime_type, &content));
+        }
+
+        let resource = self
+            .resources
+            .get(resource_name)
+            .context(format!("Resource '{resource_name}' not found"))?;
+
+        self.log(&format!(
+            "Fetching resource '{}' from path: {}",
+            resource_name, resource.path
+        ))?;
+
+        let (content, mime_type) = if is_url(&resource.path) {
+            // Handle URL resources
+            let source = resource.http_source();
+            if source.is_anonymous() {
+                self.log(&format!("Fetching URL: {}", resource.path))?;
+            } else {
+                self.log(&format!(
+                    "Fetching URL: {} with {:?}",
+                    resource.path, source
+                ))?;
+            }
+            let response = self.http.fetch(&resource.path, &source).await?;
+
+            let mime_type = response
+                .content_type
+                .as_deref()
+                .and_then(|ct| ct.split(';').next())
+                .map(|ct| ct.trim().to_string())
+                .unwrap_or_else(|| "application/octet-stream".to_string());
+            let content = response.body;
+
+            self.log(&format!(
+                "Successfully fetched {} bytes from URL, content-type: {}",
+                content.len(),
+                mime_type
+            ))?;
+            (content, mime_type)
+        } else {
+            // Handle local file resources
+            use std::fs;
+            use std::path::Path;
+            let path = Path::new(&resource.path);
+            self.log(&format!("Reading local file: {}", path.display()))?;
+            let content = fs::read(path).map_err(|e| {
+                anyhow::anyhow!("Failed to read resource file '{}': {}", resource.path, e)
+            })?;
+            let mime_type = mime_guess::from_path(path)
+                .first_or_octet_stream()
+                .essence_str()
+                .to_string();
+
+            self.log(&format!(
+                "Successfully read {} bytes from file, mime-type: {}",
+                content.len(),
+                mime_type
+            ))?;
+            (content, mime_type)
+        };
+
+        self.log(&format!(
+            "Resource '{}' processed successfully as {}",
+            resource_name,
+            if is_binary_mime(&mime_type) {
+                "binary"
+            } else {
+                "text"
+            }
+        ))?;
+
+        Ok(resource_contents(resource_uri, &mime_type, &content))
+    }
+
+    /// Build the MCP messages for a prompt, either a single user message or
+    /// the configured list of user/assistant messages
+    async fn build_prompt_messages(
+        &self,
+        prompt: &PromptConfig,
+        arguments: &HashMap<String, String>,
+    ) -> Result<Vec<Value>> {
+        let values = prompt.resolve_arguments(arguments)?;
+
+        if let Some(command) = &prompt.command {
+            let args = prompt.command_args(&values);
+            let input = command
+                .input
+                .as_ref()
+                .map(|input| render_template(input, &values));
+            let result = self
+                .execute_command(&command.tool, args.as_deref(), input.as_deref())
+                .await?;
+            if result.is_error {
+                return Err(anyhow::anyhow!(
+                    "Command '{}' exited with status {}: {}",
+                    command.tool,
+                    result.status_code,
+                    result.error.trim()
+                ));
+            }
+
+            let content = prompt.render_command_output(&result.output, &values);
+            return Ok(vec![json!({
+                "role": "user",
+                "content": {
+                    "type": "text",
+                    "text": content
+                }
+            })]);
+        }
+
+        if prompt.messages.is_empty() {
+            let content = render_template(&prompt.load_content(&self.http).await?, &values);
+            return Ok(vec![json!({
+                "role": "user",
+                "content": {
+                    "type": "text",
+                    "text": content
+                }
+            })]);
+        }
+
+        let mut messages = Vec::new();
+        for message in &prompt.messages {
+            let content = if let Some(resource_name) = &message.resource {
+                let resource_uri = format!("file://{resource_name}");
+                let resource = self.read_resource(resource_name, &resource_uri).await?;
+                json!({
+                    "type": "resource",
+                    "resource": resource
+                })
+            } else {
+                let text = render_template(&message.load_text()?.unwrap_or_default(), &values);
+                json!({
+                    "type": "text",
+                    "text": text
+                })
+            };
+
+            messages.push(json!({
+                "role": message.role.as_str(),
+                "content": content
+            }));
+        }
+
+        Ok(messages)
+    }
+
     pub async fn handle_request(&self, message: &str) -> Result<String> {
         let request: serde_json::Value = serde_json::from_str(message)?;
 
@@ -180,6 +919,14 @@ impl MyCommandMCPServer {
                         }
                     }));
                 }
+                for file in self.output_files.list() {
+                    resources.push(json!({
+                        "uri": file.uri,
+                        "name": file.name,
+                        "mimeType": file.mime_type,
+                        "size": file.size
+                    }));
+                }
                 json!({ "resources": resources })
             }
             "resources/read" => {
@@ -191,110 +938,20 @@ impl MyCommandMCPServer {
                     .context("Missing resource uri in request")?;
 
                 // Extract resource name from URI (remove file:// prefix if present)
-                let resource_name = if resource_uri.starts_with("file://") {
-                    &resource_uri[7..]
-                } else {
-                    resource_uri
-                };
-
-                let resource = self
-                    .resources
-                    .get(resource_name)
-                    .context(format!("Resource '{resource_name}' not found"))?;
-
-                self.log(&format!(
-                    "Fetching resource '{}' from path: {}",
-                    resource_name, resource.path
-                ))?;
-
-                let (content, mime_type) = if resource.path.starts_with("http://")
-                    || resource.path.starts_with("https://")
-                {
-                    // Handle URL resources
-                    self.log(&format!("Fetching URL: {}", resource.path))?;
-                    let response = reqwest::get(&resource.path).await.map_err(|e| {
-                        anyhow::anyhow!("Failed to fetch URL '{}': {}", resource.path, e)
-                    })?;
-
-                    let content_type = response
-                        .headers()
-                        .get("content-type")
-                        .and_then(|ct| ct.to_str().ok())
-                        .unwrap_or("application/octet-stream");
-
-                    let mime_type = content_type
-                        .split(';')
-                        .next()
-                        .unwrap_or("application/octet-stream")
-                        .to_string();
-                    let content = response
-                        .bytes()
-                        .await
-                        .map_err(|e| anyhow::anyhow!("Failed to read response body: {}", e))?
-                        .to_vec();
-
-                    self.log(&format!(
-                        "Successfully fetched {} bytes from URL, content-type: {}",
-                        content.len(),
-                        mime_type
-                    ))?;
-                    (content, mime_type)
-                } else {
-                    // Handle local file resources
-                    use std::fs;
-                    use std::path::Path;
-                    let path = Path::new(&resource.path);
-                    self.log(&format!("Reading local file: {}", path.display()))?;
-                    let content = fs::read(path).map_err(|e| {
-                        anyhow::anyhow!("Failed to read resource file '{}': {}", resource.path, e)
-                    })?;
-                    let mime_type = mime_guess::from_path(path)
-                        .first_or_octet_stream()
-                        .essence_str()
-                        .to_string();
-
-                    self.log(&format!(
-                        "Successfully read {} bytes from file, mime-type: {}",
-                        content.len(),
-                        mime_type
-                    ))?;
-                    (content, mime_type)
-                };
-
-                let is_binary = !mime_type.starts_with("text/")
-                    && mime_type != "application/json"
-                    && mime_type != "application/xml";
-
-                self.log(&format!(
-                    "Resource '{}' processed successfully as {}",
-                    resource_name,
-                    if is_binary { "binary" } else { "text" }
-                ))?;
+                let resource_name = resource_uri.strip_prefix("file://").unwrap_or(resource_uri);
 
-                if is_binary {
-                    let encoded_content =
-                        base64::engine::general_purpose::STANDARD.encode(&content);
-                    json!({
-                        "contents": [{
-                            "uri": resource_uri,
-                            "mimeType": mime_type,
-                            "blob": encoded_content
-                        }]
-                    })
-                } else {
-                    let text_content = String::from_utf8_lossy(&content).to_string();
-                    json!({
-                        "contents": [{
-                            "uri": resource_uri,
-                            "mimeType": mime_type,
-                            "text": text_content
-                        }]
-                    })
-                }
+                let contents = self.read_resource(resource_name, resource_uri).await?;
+                json!({ "contents": [contents] })
             }
             "initialize" => {
+                let requested = request["params"]["protocolVersion"].as_str();
+                let version = self.negotiate_protocol_version(requested);
+                self.log(&format!(
+                    "Client requested protocol version {}, using {version}",
+                    requested.unwrap_or("none")
+                ))?;
                 json!({
-                    "protocolVersion": "2024-11-05",
+                    "protocolVersion": version,
                     "capabilities": {
                         "tools": {},
                         "prompts": {},
@@ -334,14 +991,21 @@ impl MyCommandMCPServer {
                         );
                     }
 
-                    tools.push(json!({
+                    let mut tool = json!({
                         "name": tool_config.name,
                         "description": tool_config.description,
                         "inputSchema": {
                             "type": "object",
                             "properties": properties
                         }
-                    }));
+                    });
+                    if let Some(schema) = tool_config
+                        .output_schema()
+                        .filter(|_| self.supports(STRUCTURED_OUTPUT_VERSION))
+                    {
+                        tool["outputSchema"] = schema;
+                    }
+                    tools.push(tool);
                 }
 
                 json!({ "tools": tools })
@@ -368,47 +1032,62 @@ impl MyCommandMCPServer {
 
                 // Create response based on content type
                 if result.is_binary || result.content_type.is_some() {
-                    // Return as resource/file content
-                    let mut content = json!({
-                        "type": "resource",
-                        "resource": {
-                            "uri": format!("data:{}", result.content_type.as_deref().unwrap_or("application/octet-stream")),
-                            "mimeType": result.content_type.as_deref().unwrap_or("application/octet-stream")
-                        }
-                    });
-
-                    // If binary, the output is already base64 encoded
-                    if result.is_binary {
-                        content["resource"]["uri"] = json!(format!(
-                            "data:{};base64,{}",
-                            result
-                                .content_type
-                                .as_deref()
-                                .unwrap_or("application/octet-stream"),
-                            result.output
-                        ));
-                    } else {
-                        content["resource"]["text"] = json!(result.output);
-                    }
-
-                    // Add content disposition if available
-                    if let Some(disposition) = &result.content_disposition {
-                        content["resource"]["contentDisposition"] = json!(disposition);
-                    }
-
+                    let mut content = vec![typed_content(
+                        tool_name,
+                        &result,
+                        self.supports(AUDIO_CONTENT_VERSION),
+                    )];
+                    content.extend(self.output_file_content(&result)?);
                     json!({
-                        "content": [content],
-                        "isError": result.status_code != 0
+                        "content": content,
+                        "isError": result.is_error,
+                        "_meta": result_meta(&result)
                     })
                 } else {
-                    // Return as plain text (existing behavior)
-                    json!({
-                        "content": [{
+                    let tool = self.tools.get(tool_name);
+                    let format = tool.and_then(|tool| tool.output_format.as_ref());
+
+                    // Parse the output of successful commands into structured
+                    // content, keeping the plain text for older clients
+                    let structured = match format {
+                        Some(format) if !result.is_error => {
+                            Some(format.parse(&result.output).map_err(|e| {
+                                format!("Failed to parse output as {}: {e:#}", format.name())
+                            }))
+                        }
+                        _ => None,
+                    };
+
+                    let mut content = match tool.map(|tool| tool.presentation) {
+                        Some(Presentation::Blocks) => result_blocks(&result),
+                        _ => vec![json!({
                             "type": "text",
                             "text": serde_json::to_string_pretty(&result)?
-                        }],
-                        "isError": result.status_code != 0
-                    })
+                        })],
+                    };
+                    content.extend(self.output_file_content(&result)?);
+                    let mut is_error = result.is_error;
+                    let structured_content = match structured {
+                        Some(Ok(value)) => Some(value),
+                        Some(Err(message)) => {
+                            content.push(json!({ "type": "text", "text": message }));
+                            is_error = true;
+                            None
+                        }
+                        None => None,
+                    };
+
+                    let mut response = json!({
+                        "content": content,
+                        "isError": is_error,
+                        "_meta": result_meta(&result)
+                    });
+                    if let Some(value) =
+                        structured_content.filter(|_| self.supports(STRUCTURED_OUTPUT_VERSION))
+                    {
+                        response["structuredContent"] = value;
+                    }
+                    response
                 }
             }
             "prompts/list" => {
@@ -416,10 +1095,22 @@ impl MyCommandMCPServer {
                     .prompts
                     .values()
                     .map(|p| {
-                        json!({
+                        let mut entry = json!({
                             "name": p.name,
                             "description": p.description
-                        })
+                        });
+                        if !p.arguments.is_empty() {
+                            entry["arguments"] = json!(p
+                                .arguments
+                                .iter()
+                                .map(|a| json!({
+                                    "name": a.name,
+                                    "description": a.description,
+                                    "required": a.required
+                                }))
+                                .collect::<Vec<_>>());
+                        }
+                        entry
                     })
                     .collect::<Vec<_>>();
 
@@ -435,18 +1126,29 @@ impl MyCommandMCPServer {
                     .as_str()
                     .context("Missing prompt name in request")?;
 
+                let arguments = params
+                    .get("arguments")
+                    .and_then(|v| v.as_object())
+                    .map(|arguments| {
+                        arguments
+                            .iter()
+                            .map(|(key, value)| {
+                                let value = match value.as_str() {
+                                    Some(text) => text.to_string(),
+                                    None => value.to_string(),
+                                };
+                                (key.clone(), value)
+                            })
+                            .collect::<HashMap<_, _>>()
+                    })
+                    .unwrap_or_default();
+
                 if let Some(prompt) = self.prompts.get(prompt_name) {
-                    match prompt.load_content() {
-                        Ok(content) => json!({
+                    match self.build_prompt_messages(prompt, &arguments).await {
+                        Ok(messages) => json!({
                             "name": prompt.name,
                             "description": prompt.description,
-                            "messages": [{
-                                "role": "user",
-                                "content": {
-                                    "type": "text",
-                                    "text": content
-                                }
-                            }]
+                            "messages": messages
                         }),
                         Err(e) => {
                             self.log(&format!(
@@ -502,3 +1204,528 @@ impl MyCommandMCPServer {
         .to_string())
     }
 }
+
+/// Write an inline script to a temp file readable only by the current user
+fn write_script(script: &str) -> Result<tempfile::TempPath> {
+    use std::io::Write;
+
+    let mut file = tempfile::Builder::new()
+        .prefix("mycommandmcp-script-")
+        .tempfile()
+        .context("Failed to create temp file for inline script")?;
+    file.write_all(script.as_bytes())
+        .context("Failed to write inline script")?;
+    Ok(file.into_temp_path())
+}
+
+/// Bind a parsed JSON value to template variables, using dotted paths for
+/// object keys and array indices, e.g. `prefix.items.0.name`
+fn bind_json(variables: &mut HashMap<String, String>, prefix: &str, value: &Value) {
+    match value {
+        Value::Object(map) => {
+            for (key, item) in map {
+                bind_json(variables, &format!("{prefix}.{key}"), item);
+            }
+        }
+        Value::Array(items) => {
+            for (index, item) in items.iter().enumerate() {
+                bind_json(variables, &format!("{prefix}.{index}"), item);
+            }
+        }
+        _ => {}
+    }
+
+    // Objects and arrays are also bound as a whole, in their JSON form
+    let text = match value {
+        Value::String(text) => text.clone(),
+        other => other.to_string(),
+    };
+    variables.insert(prefix.to_string(), text);
+}
+
+/// Signal that terminated a process, on Unix
+#[cfg(unix)]
+fn exit_signal(status: &ExitStatus) -> Option<i32> {
+    use std::os::unix::process::ExitStatusExt;
+    status.signal()
+}
+
+#[cfg(not(unix))]
+fn exit_signal(_status: &ExitStatus) -> Option<i32> {
+    None
+}
+
+/// Signal received by a process writing to a pipe whose reader has exited
+const SIGPIPE: i32 = 13;
+
+fn is_binary_mime(mime_type: &str) -> bool {
+    !mime_type.starts_with("text/")
+        && mime_type != "application/json"
+        && mime_type != "application/xml"
+}
+
+/// Build the contents item of `resources/read`, base64 encoding binary data
+fn resource_contents(uri: &str, mime_type: &str, content: &[u8]) -> Value {
+    if is_binary_mime(mime_type) {
+        let encoded_content = base64::engine::general_purpose::STANDARD.encode(content);
+        json!({
+            "uri": uri,
+            "mimeType": mime_type,
+            "blob": encoded_content
+        })
+    } else {
+        let text_content = String::from_utf8_lossy(content).to_string();
+        json!({
+            "uri": uri,
+            "mimeType": mime_type,
+            "text": text_content
+        })
+    }
+}
+
+/// Content item for output with a content type: MCP `image` and `audio`
+/// content for media, an embedded resource for other types and for audio
+/// when the client does not support `audio` content
+fn typed_content(tool_name: &str, result: &CommandResult, audio: bool) -> Value {
+    let mime_type = result
+        .content_type
+        .as_deref()
+        .unwrap_or("application/octet-stream");
+
+    // The output is already base64 encoded for binary content types
+    if result.is_binary {
+        if mime_type.starts_with("image/") {
+            return json!({ "type": "image", "data": result.output, "mimeType": mime_type });
+        }
+        if audio && mime_type.starts_with("audio/") {
+            return json!({ "type": "audio", "data": result.output, "mimeType": mime_type });
+        }
+    }
+
+    // Name the output after the filename of the content disposition, or
+    // after the tool with an extension matching the content type
+    let filename = result
+        .content_disposition
+        .as_deref()
+        .and_then(|disposition| disposition.split_once("filename="))
+        .map(|(_, name)| {
+            name.split(';')
+                .next()
+                .unwrap_or(name)
+                .trim()
+                .trim_matches('"')
+                .to_string()
+        })
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| {
+            match mime_guess::get_mime_extensions_str(mime_type).and_then(|exts| exts.first()) {
+                Some(ext) => format!("output.{ext}"),
+                None => "output".to_string(),
+            }
+        });
+
+    let mut resource = json!({
+        "uri": format!("tool://{tool_name}/{filename}"),
+        "mimeType": mime_type
+    });
+    if result.is_binary {
+        resource["blob"] = json!(result.output);
+    } else {
+        resource["text"] = json!(result.output);
+    }
+
+    // Add content disposition if available
+    if let Some(disposition) = &result.content_disposition {
+        resource["contentDisposition"] = json!(disposition);
+    }
+
+    json!({ "type": "resource", "resource": resource })
+}
+
+/// Execution details returned in the `_meta` of a tool result
+fn result_meta(result: &CommandResult) -> Value {
+    let mut meta = json!({
+        "durationMs": result.duration_ms,
+        // Processes killed by a signal have no exit code
+        "exitCode": result.signal.is_none().then_some(result.status_code),
+        "signal": result.signal,
+        "truncated": result.truncated
+    });
+    if let Some(steps) = &result.steps {
+        meta["steps"] = json!(steps);
+    }
+    if let Some(dir) = &result.kept_dir {
+        meta["keptWorkingDir"] = json!(dir.display().to_string());
+    }
+    meta
+}
+
+/// Content blocks for stdout, stderr and the exit status, shown as plain
+/// multi-line text instead of a JSON string
+fn result_blocks(result: &CommandResult) -> Vec<Value> {
+    let mut blocks = Vec::new();
+    if !result.output.is_empty() {
+        blocks.push(json!({ "type": "text", "text": result.output }));
+    }
+    if !result.error.trim().is_empty() {
+        blocks.push(json!({ "type": "text", "text": format!("stderr:\n{}", result.error) }));
+    }
+
+    let mut status = match result.signal {
+        Some(signal) => format!("Terminated by signal {signal}"),
+        None => format!("Exit status: {}", result.status_code),
+    };
+    if let Some(meaning) = &result.exit_meaning {
+        status.push_str(&format!(" ({meaning})"));
+    }
+    if result.truncated {
+        status.push_str(", output truncated");
+    }
+    for step in result.steps.iter().flatten() {
+        let label = match &step.name {
+            Some(name) => format!("{name} ({})", step.command),
+            None => step.command.clone(),
+        };
+        match step.status_code {
+            Some(code) => status.push_str(&format!("\n  {label}: exit status {code}")),
+            None => status.push_str(&format!("\n  {label}: skipped")),
+        }
+    }
+    blocks.push(json!({ "type": "text", "text": status }));
+    blocks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli_parser::load_config;
+
+    /// A server for the tools of a YAML configuration
+    async fn server(config: &str) -> MyCommandMCPServer {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.yaml");
+        std::fs::write(&path, config).unwrap();
+        let config = load_config(path.to_str().unwrap(), None).await.unwrap();
+        MyCommandMCPServer::new(config, DualLogger::new(None).unwrap())
+    }
+
+    #[tokio::test]
+    async fn output_files_of_tool_name_with_space() {
+        let server = server(
+            r#"
+tools:
+  - name: "make archive"
+    description: "Write a file"
+    script: "echo done > out.txt"
+    path: "/"
+    accepts_args: false
+    accept_input: false
+    output_files: ["*.txt"]
+"#,
+        )
+        .await;
+        let result = server
+            .execute_command("make archive", None, None)
+            .await
+            .unwrap();
+        assert_eq!(result.status_code, 0, "{}", result.error);
+        assert_eq!(result.output_files.len(), 1);
+        let file = &result.output_files[0];
+        assert!(
+            file.uri.starts_with("tool://output/make%20archive/"),
+            "{}",
+            file.uri
+        );
+        assert!(file.uri.ends_with("/out.txt"), "{}", file.uri);
+        let contents = server.read_resource("", &file.uri).await.unwrap();
+        assert_eq!(contents["text"], "done\n");
+    }
+
+    const PIPELINE_CONFIG: &str = r#"
+tools:
+  - name: "count"
+    description: "Sort, number and count lines"
+    path: "/"
+    accepts_args: false
+    accept_input: true
+    pipeline:
+      - command: "sort"
+      - command: "uniq"
+        args: "-c"
+      - command: "wc"
+        args: "-l"
+  - name: "broken"
+    description: "A failing middle step"
+    path: "/"
+    accepts_args: false
+    accept_input: false
+    pipeline:
+      - command: "echo"
+        args: "hello"
+      - command: "false"
+      - command: "cat"
+  - name: "first_line"
+    description: "First line of a long output"
+    path: "/"
+    accepts_args: false
+    accept_input: false
+    pipeline:
+      - command: "yes"
+      - command: "head"
+        args: "-1"
+"#;
+
+    #[tokio::test]
+    async fn pipeline_connects_steps() {
+        let server = server(PIPELINE_CONFIG).await;
+        let result = server
+            .execute_command("count", None, Some("b\na\nb\n"))
+            .await
+            .unwrap();
+        assert_eq!(result.status_code, 0, "{}", result.error);
+        assert!(!result.is_error);
+        assert_eq!(result.output.trim(), "2");
+        let steps = result.steps.unwrap();
+        assert_eq!(steps.len(), 3);
+        assert!(steps.iter().all(|step| step.status_code == Some(0)));
+    }
+
+    #[tokio::test]
+    async fn pipeline_reports_failing_middle_step() {
+        let server = server(PIPELINE_CONFIG).await;
+        let result = server.execute_command("broken", None, None).await.unwrap();
+        assert_eq!(result.status_code, 1);
+        assert!(result.is_error);
+        let steps = result.steps.unwrap();
+        assert_eq!(steps[1].command, "false");
+        assert_eq!(steps[1].status_code, Some(1));
+        assert_eq!(steps[2].status_code, Some(0));
+    }
+
+    #[tokio::test]
+    async fn pipeline_ignores_steps_stopped_by_early_exit() {
+        let server = server(PIPELINE_CONFIG).await;
+        let result = server
+            .execute_command("first_line", None, None)
+            .await
+            .unwrap();
+        assert_eq!(result.status_code, 0, "{}", result.error);
+        assert!(!result.is_error);
+        assert_eq!(result.output, "y\n");
+    }
+
+    const WORKFLOW_CONFIG: &str = r#"
+tools:
+  - name: "greet"
+    description: "Print a greeting"
+    command: "echo"
+    path: "/"
+    accepts_args: true
+    accept_input: false
+  - name: "relay"
+    description: "Pass a greeting through the steps"
+    path: "/"
+    accepts_args: true
+    accept_input: false
+    workflow:
+      - name: "greet"
+        tool: "greet"
+        args: "hello {{args}}"
+      - name: "copy"
+        command: "cat"
+        input: "{{steps.greet.output}}"
+      - name: "on_failure"
+        if: "{{steps.greet.status_code}} != 0"
+        command: "echo"
+        args: "failed"
+  - name: "tolerant"
+    description: "Continue after a failing step"
+    path: "/"
+    accepts_args: false
+    accept_input: false
+    workflow:
+      - name: "check"
+        command: "false"
+        continue_on_error: true
+      - name: "report"
+        command: "echo"
+        args: "check exited with {{steps.check.status_code}}"
+  - name: "strict"
+    description: "Stop at a failing step"
+    path: "/"
+    accepts_args: false
+    accept_input: false
+    workflow:
+      - name: "check"
+        command: "false"
+      - name: "report"
+        command: "echo"
+        args: "unreachable"
+"#;
+
+    #[tokio::test]
+    async fn workflow_passes_step_output_and_skips_steps() {
+        let server = server(WORKFLOW_CONFIG).await;
+        let result = server
+            .execute_command("relay", Some("world"), None)
+            .await
+            .unwrap();
+        assert_eq!(result.status_code, 0, "{}", result.error);
+        assert!(!result.is_error);
+        assert_eq!(result.output, "hello world\n");
+        let steps = result.steps.unwrap();
+        assert_eq!(steps.len(), 3);
+        assert_eq!(steps[1].status_code, Some(0));
+        assert_eq!(steps[2].name.as_deref(), Some("on_failure"));
+        assert_eq!(steps[2].status_code, None);
+    }
+
+    #[tokio::test]
+    async fn workflow_continues_after_failing_step_on_error() {
+        let server = server(WORKFLOW_CONFIG).await;
+        let result = server
+            .execute_command("tolerant", None, None)
+            .await
+            .unwrap();
+        assert_eq!(result.status_code, 0, "{}", result.error);
+        assert!(!result.is_error);
+        assert_eq!(result.output, "check exited with 1\n");
+        let steps = result.steps.unwrap();
+        assert_eq!(steps[0].status_code, Some(1));
+        assert_eq!(steps[1].status_code, Some(0));
+    }
+
+    #[tokio::test]
+    async fn workflow_stops_at_failing_step() {
+        let server = server(WORKFLOW_CONFIG).await;
+        let result = server.execute_command("strict", None, None).await.unwrap();
+        assert_eq!(result.status_code, 1);
+        assert!(result.is_error);
+        let steps = result.steps.unwrap();
+        assert_eq!(steps.len(), 1);
+        assert_eq!(steps[0].status_code, Some(1));
+    }
+
+    #[tokio::test]
+    async fn inline_script_receives_args_and_input() {
+        let server = server(
+            r#"
+tools:
+  - name: "greet"
+    description: "Greet with a script"
+    script: |
+      read -r greeting
+      echo "$greeting $1 and $2 ($#)"
+    path: "/"
+    accepts_args: true
+    accept_input: true
+"#,
+        )
+        .await;
+        let result = server
+            .execute_command("greet", Some("alice bob"), Some("hello\n"))
+            .await
+            .unwrap();
+        assert_eq!(result.status_code, 0, "{}", result.error);
+        assert_eq!(result.output, "hello alice and bob (2)\n");
+    }
+
+    const EXIT_CODE_CONFIG: &str = r#"
+tools:
+  - name: "compare"
+    description: "Exit with the given code"
+    script: "exit $1"
+    path: "/"
+    accepts_args: true
+    accept_input: false
+    success_codes: [0, 1]
+    exit_codes:
+      1: "files differ"
+  - name: "warn"
+    description: "Write to stderr"
+    script: "echo $1 >&2"
+    path: "/"
+    accepts_args: true
+    accept_input: false
+    stderr_is_error: true
+"#;
+
+    #[tokio::test]
+    async fn success_codes_decide_failure() {
+        let server = server(EXIT_CODE_CONFIG).await;
+        let result = server
+            .execute_command("compare", Some("1"), None)
+            .await
+            .unwrap();
+        assert_eq!(result.status_code, 1);
+        assert!(!result.is_error);
+        assert_eq!(result.exit_meaning.as_deref(), Some("files differ"));
+
+        let result = server
+            .execute_command("compare", Some("2"), None)
+            .await
+            .unwrap();
+        assert_eq!(result.status_code, 2);
+        assert!(result.is_error);
+        assert_eq!(result.exit_meaning, None);
+    }
+
+    #[tokio::test]
+    async fn stderr_is_error_fails_on_stderr_output() {
+        let server = server(EXIT_CODE_CONFIG).await;
+        let result = server
+            .execute_command("warn", Some("warning"), None)
+            .await
+            .unwrap();
+        assert_eq!(result.status_code, 0);
+        assert!(result.is_error);
+
+        let result = server.execute_command("warn", None, None).await.unwrap();
+        assert_eq!(result.status_code, 0);
+        assert!(!result.is_error, "whitespace on stderr is not an error");
+    }
+
+    #[tokio::test]
+    async fn scratch_dir_is_removed_unless_kept_on_failure() {
+        let project = tempfile::tempdir().unwrap();
+        std::fs::create_dir(project.path().join("template")).unwrap();
+        std::fs::write(project.path().join("template/seed.txt"), "seed\n").unwrap();
+        let server = server(&format!(
+            r#"
+tools:
+  - name: "build"
+    description: "Print the working directory, fail unless asked to succeed"
+    script: |
+      pwd
+      cat seed.txt
+      test "$1" = ok
+    path: "{}"
+    accepts_args: true
+    accept_input: false
+    scratch:
+      template: "template"
+      keep_on_failure: true
+"#,
+            project.path().display()
+        ))
+        .await;
+
+        let result = server
+            .execute_command("build", Some("ok"), None)
+            .await
+            .unwrap();
+        assert_eq!(result.status_code, 0, "{}", result.error);
+        let (dir, seed) = result.output.split_once('\n').unwrap();
+        assert_eq!(seed, "seed\n");
+        assert!(!Path::new(dir).starts_with(project.path()));
+        assert!(!Path::new(dir).exists());
+        assert_eq!(result.kept_dir, None);
+
+        let result = server.execute_command("build", None, None).await.unwrap();
+        assert!(result.is_error);
+        let kept = result.kept_dir.unwrap();
+        assert!(kept.join("seed.txt").is_file());
+        assert_eq!(result.output.lines().next(), kept.to_str());
+        std::fs::remove_dir_all(kept).unwrap();
+    }
+}
@@ -0,0 +1,108 @@
+/// Detect the MIME type of binary data from its leading magic bytes.
+/// Returns `None` for text and unrecognized data.
+pub fn sniff_mime(data: &[u8]) -> Option<&'static str> {
+    const SIGNATURES: &[(&[u8], &str)] = &[
+        (b"\x89PNG\r\n\x1a\n", "image/png"),
+        (b"\xff\xd8\xff", "image/jpeg"),
+        (b"GIF87a", "image/gif"),
+        (b"GIF89a", "image/gif"),
+        (b"II*\x00", "image/tiff"),
+        (b"MM\x00*", "image/tiff"),
+        (b"%PDF-", "application/pdf"),
+        (b"PK\x03\x04", "application/zip"),
+        (b"\x1f\x8b", "application/gzip"),
+        (b"\xfd7zXZ\x00", "application/x-xz"),
+        (b"7z\xbc\xaf\x27\x1c", "application/x-7z-compressed"),
+        (b"\x28\xb5\x2f\xfd", "application/zstd"),
+        (b"\xff\xfb", "audio/mpeg"),
+        (b"\xff\xf3", "audio/mpeg"),
+        (b"\xff\xf2", "audio/mpeg"),
+        (b"OggS", "audio/ogg"),
+        (b"fLaC", "audio/flac"),
+        (b"\x7fELF", "application/x-executable"),
+        (b"SQLite format 3\x00", "application/vnd.sqlite3"),
+    ];
+
+    if let Some((_, mime)) = SIGNATURES
+        .iter()
+        .find(|(signature, _)| data.starts_with(signature))
+    {
+        return Some(mime);
+    }
+
+    // Short signatures that could start plain text are checked with the byte after them
+    if data.len() >= 4 && data.starts_with(b"BZh") && data[3].is_ascii_digit() {
+        return Some("application/x-bzip2");
+    }
+    if data.len() >= 4 && data.starts_with(b"ID3") && (2..=4).contains(&data[3]) {
+        return Some("audio/mpeg");
+    }
+
+    // RIFF containers carry their format at offset 8
+    if data.len() >= 12 && data.starts_with(b"RIFF") {
+        match &data[8..12] {
+            b"WAVE" => return Some("audio/wav"),
+            b"WEBP" => return Some("image/webp"),
+            _ => {}
+        }
+    }
+
+    // ISO base media files have an `ftyp` box at offset 4
+    if data.len() >= 12 && &data[4..8] == b"ftyp" {
+        return match &data[8..12] {
+            b"M4A " => Some("audio/mp4"),
+            b"avif" => Some("image/avif"),
+            b"heic" | b"heix" => Some("image/heic"),
+            _ => Some("video/mp4"),
+        };
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_common_signatures() {
+        assert_eq!(sniff_mime(b"\x89PNG\r\n\x1a\n\x00\x00"), Some("image/png"));
+        assert_eq!(sniff_mime(b"%PDF-1.7\n"), Some("application/pdf"));
+        assert_eq!(sniff_mime(b"\x1f\x8b\x08\x00"), Some("application/gzip"));
+        assert_eq!(
+            sniff_mime(b"\x7fELF\x02\x01\x01"),
+            Some("application/x-executable")
+        );
+    }
+
+    #[test]
+    fn detects_container_formats() {
+        assert_eq!(
+            sniff_mime(b"RIFF\x24\x00\x00\x00WAVEfmt "),
+            Some("audio/wav")
+        );
+        assert_eq!(
+            sniff_mime(b"RIFF\x24\x00\x00\x00WEBPVP8 "),
+            Some("image/webp")
+        );
+        assert_eq!(sniff_mime(b"RIFF\x24\x00\x00\x00AVI LIST"), None);
+        assert_eq!(sniff_mime(b"\x00\x00\x00\x20ftypisom"), Some("video/mp4"));
+        assert_eq!(sniff_mime(b"\x00\x00\x00\x20ftypM4A "), Some("audio/mp4"));
+    }
+
+    #[test]
+    fn text_starting_like_a_short_signature_is_not_binary() {
+        assert_eq!(sniff_mime(b"BZh9"), Some("application/x-bzip2"));
+        assert_eq!(sniff_mime(b"BZhello"), None);
+        assert_eq!(sniff_mime(b"ID3\x03"), Some("audio/mpeg"));
+        assert_eq!(sniff_mime(b"ID3 tags"), None);
+    }
+
+    #[test]
+    fn ignores_text_and_short_data() {
+        assert_eq!(sniff_mime(b"hello world\n"), None);
+        assert_eq!(sniff_mime(b"{\"a\": 1}"), None);
+        assert_eq!(sniff_mime(b""), None);
+        assert_eq!(sniff_mime(b"RIFF"), None);
+    }
+}
@@ -0,0 +1,157 @@
+use anyhow::{Context, Result};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// A file written by a tool call, served as a temporary resource
+#[derive(Debug, Clone)]
+pub struct OutputFile {
+    pub uri: String,
+    pub name: String,
+    pub path: PathBuf,
+    pub mime_type: String,
+    pub size: u64,
+}
+
+/// The working directory of a tool call and the files registered from it
+struct OutputDir {
+    dir: PathBuf,
+    expires: Instant,
+    files: Vec<OutputFile>,
+}
+
+/// Files produced by tool calls, kept until they expire
+#[derive(Default)]
+pub struct OutputFiles {
+    dirs: Mutex<Vec<OutputDir>>,
+}
+
+/// Longest time output files can be kept, a year
+pub const MAX_OUTPUT_TTL_SECS: u64 = 365 * 24 * 60 * 60;
+
+/// Check that output file patterns are valid globs that stay inside the working directory
+pub fn validate_patterns(patterns: &[String]) -> Result<()> {
+    for pattern in patterns {
+        if Path::new(pattern).is_absolute() || pattern.split(['/', '\\']).any(|part| part == "..") {
+            return Err(anyhow::anyhow!(
+                "Output file pattern must be relative to the working directory: {pattern}"
+            ));
+        }
+        glob::Pattern::new(pattern).context(format!("Invalid output file pattern: {pattern}"))?;
+    }
+    Ok(())
+}
+
+impl OutputFiles {
+    /// Register the files of `dir` matching `patterns` as resources of `tool_name`
+    /// that expire after `ttl`. The directory is removed when they expire, or
+    /// right away when no file matches.
+    pub fn register(
+        &self,
+        tool_name: &str,
+        dir: tempfile::TempDir,
+        patterns: &[String],
+        ttl: Duration,
+    ) -> Result<Vec<OutputFile>> {
+        self.purge();
+
+        let dir_name = dir
+            .path()
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+
+        let mut seen = HashSet::new();
+        let mut files = Vec::new();
+        for pattern in patterns {
+            let full_pattern = dir.path().join(pattern);
+            let matches = glob::glob(&full_pattern.to_string_lossy())
+                .context(format!("Invalid output file pattern: {pattern}"))?;
+            for path in matches.flatten() {
+                if !path.is_file() || !seen.insert(path.clone()) {
+                    continue;
+                }
+                let relative = path.strip_prefix(dir.path()).unwrap_or(&path);
+
+                // The tool name goes in the path, where any name can be percent-encoded
+                let mut uri = reqwest::Url::parse("tool://output/").context("Invalid URI")?;
+                if let Ok(mut segments) = uri.path_segments_mut() {
+                    segments.clear().push(tool_name).push(&dir_name);
+                    segments.extend(relative.iter().map(|part| part.to_string_lossy()));
+                }
+
+                files.push(OutputFile {
+                    uri: uri.to_string(),
+                    name: relative.to_string_lossy().to_string(),
+                    mime_type: mime_guess::from_path(&path)
+                        .first_or_octet_stream()
+                        .essence_str()
+                        .to_string(),
+                    size: fs::metadata(&path).map(|m| m.len()).unwrap_or(0),
+                    path,
+                });
+            }
+        }
+
+        // Dropping the directory without matches removes it
+        if files.is_empty() {
+            return Ok(files);
+        }
+
+        let expires = Instant::now()
+            .checked_add(ttl)
+            .context("Output file lifetime is too long")?;
+        self.lock().push(OutputDir {
+            dir: dir.keep(),
+            expires,
+            files: files.clone(),
+        });
+        Ok(files)
+    }
+
+    /// Look up an output file by its resource URI
+    pub fn get(&self, uri: &str) -> Option<OutputFile> {
+        self.purge();
+        self.lock()
+            .iter()
+            .flat_map(|dir| dir.files.iter())
+            .find(|file| file.uri == uri)
+            .cloned()
+    }
+
+    /// The output files that have not expired
+    pub fn list(&self) -> Vec<OutputFile> {
+        self.purge();
+        self.lock()
+            .iter()
+            .flat_map(|dir| dir.files.iter().cloned())
+            .collect()
+    }
+
+    /// Remove the directories whose files have expired
+    pub fn purge(&self) {
+        let now = Instant::now();
+        self.lock().retain(|dir| {
+            if dir.expires > now {
+                return true;
+            }
+            let _ = fs::remove_dir_all(&dir.dir);
+            false
+        });
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<OutputDir>> {
+        // A panic while holding the lock cannot leave the list inconsistent
+        self.dirs.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Drop for OutputFiles {
+    fn drop(&mut self) {
+        for dir in self.lock().iter() {
+            let _ = fs::remove_dir_all(&dir.dir);
+        }
+    }
+}
@@ -0,0 +1,225 @@
+use anyhow::{Context, Result};
+use regex::Regex;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+
+/// How the stdout of a tool is parsed into MCP structured content
+#[derive(Debug, Deserialize, Clone, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputFormat {
+    /// A single JSON document
+    Json,
+    /// One JSON document per line
+    Jsonl,
+    /// Comma-separated values with a header row
+    Csv,
+    /// Tab-separated values with a header row
+    Tsv,
+    /// `key: value` or `key=value` lines
+    KeyValue,
+    /// A regular expression whose named groups are captured for every match
+    Regex(String),
+}
+
+impl OutputFormat {
+    pub fn name(&self) -> &'static str {
+        match self {
+            OutputFormat::Json => "json",
+            OutputFormat::Jsonl => "jsonl",
+            OutputFormat::Csv => "csv",
+            OutputFormat::Tsv => "tsv",
+            OutputFormat::KeyValue => "key_value",
+            OutputFormat::Regex(_) => "regex",
+        }
+    }
+
+    /// Check the format settings, such as the regular expression
+    pub fn validate(&self) -> Result<()> {
+        if let OutputFormat::Regex(pattern) = self {
+            let regex = Regex::new(pattern).context(format!("Invalid regex: {pattern}"))?;
+            if regex.capture_names().flatten().next().is_none() {
+                return Err(anyhow::anyhow!(
+                    "Regex has no named groups, use (?P<name>...): {pattern}"
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// JSON Schema of the structured content produced by this format
+    pub fn schema(&self) -> Value {
+        let rows = |key: &str| {
+            json!({
+                "type": "object",
+                "properties": {
+                    key: {
+                        "type": "array",
+                        "items": {
+                            "type": "object",
+                            "additionalProperties": { "type": "string" }
+                        }
+                    }
+                },
+                "required": [key]
+            })
+        };
+
+        match self {
+            OutputFormat::Json => json!({ "type": "object" }),
+            OutputFormat::Jsonl => json!({
+                "type": "object",
+                "properties": { "items": { "type": "array" } },
+                "required": ["items"]
+            }),
+            OutputFormat::Csv | OutputFormat::Tsv => rows("rows"),
+            OutputFormat::KeyValue => json!({
+                "type": "object",
+                "additionalProperties": { "type": "string" }
+            }),
+            OutputFormat::Regex(_) => rows("matches"),
+        }
+    }
+
+    /// Parse the output of a command into a JSON object
+    pub fn parse(&self, output: &str) -> Result<Value> {
+        match self {
+            OutputFormat::Json => {
+                let value: Value = serde_json::from_str(output).context("Invalid JSON")?;
+                // Structured content must be an object
+                Ok(match value {
+                    Value::Object(_) => value,
+                    other => json!({ "result": other }),
+                })
+            }
+            OutputFormat::Jsonl => {
+                let items = output
+                    .lines()
+                    .enumerate()
+                    .filter(|(_, line)| !line.trim().is_empty())
+                    .map(|(index, line)| {
+                        serde_json::from_str::<Value>(line)
+                            .context(format!("Invalid JSON on line {}", index + 1))
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                Ok(json!({ "items": items }))
+            }
+            OutputFormat::Csv => parse_delimited(output, b','),
+            OutputFormat::Tsv => parse_delimited(output, b'\t'),
+            OutputFormat::KeyValue => {
+                let mut map = Map::new();
+                for line in output.lines().filter(|line| !line.trim().is_empty()) {
+                    // Split on whichever separator comes first
+                    let separator = line
+                        .find([':', '='])
+                        .context(format!("Missing ':' or '=' in line: {line}"))?;
+                    let key = line[..separator].trim();
+                    let value = line[separator + 1..].trim();
+                    map.insert(key.to_string(), Value::String(value.to_string()));
+                }
+                Ok(Value::Object(map))
+            }
+            OutputFormat::Regex(pattern) => {
+                let regex = Regex::new(pattern).context(format!("Invalid regex: {pattern}"))?;
+                let names: Vec<&str> = regex.capture_names().flatten().collect();
+                let matches: Vec<Value> = regex
+                    .captures_iter(output)
+                    .map(|captures| {
+                        let mut map = Map::new();
+                        for name in &names {
+                            if let Some(group) = captures.name(name) {
+                                map.insert(name.to_string(), json!(group.as_str()));
+                            }
+                        }
+                        Value::Object(map)
+                    })
+                    .collect();
+                Ok(json!({ "matches": matches }))
+            }
+        }
+    }
+}
+
+/// Parse CSV or TSV with a header row into a list of objects
+fn parse_delimited(output: &str, delimiter: u8) -> Result<Value> {
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(delimiter)
+        .from_reader(output.as_bytes());
+    let headers = reader.headers().context("Invalid header row")?.clone();
+
+    let mut rows = Vec::new();
+    for record in reader.records() {
+        let record = record.context("Invalid row")?;
+        let row: Map<String, Value> = headers
+            .iter()
+            .zip(record.iter())
+            .map(|(header, value)| (header.to_string(), json!(value)))
+            .collect();
+        rows.push(Value::Object(row));
+    }
+
+    Ok(json!({ "rows": rows }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn json_is_wrapped_unless_it_is_an_object() {
+        let format = OutputFormat::Json;
+        assert_eq!(format.parse(r#"{"a": 1}"#).unwrap(), json!({ "a": 1 }));
+        assert_eq!(format.parse("[1, 2]").unwrap(), json!({ "result": [1, 2] }));
+        assert!(format.parse("not json").is_err());
+    }
+
+    #[test]
+    fn jsonl_skips_blank_lines_and_reports_the_bad_line() {
+        let format = OutputFormat::Jsonl;
+        let parsed = format.parse("{\"a\": 1}\n\n{\"a\": 2}\n").unwrap();
+        assert_eq!(parsed, json!({ "items": [{ "a": 1 }, { "a": 2 }] }));
+
+        let error = format.parse("{}\n{oops\n").unwrap_err();
+        assert!(error.to_string().contains("line 2"));
+    }
+
+    #[test]
+    fn delimited_rows_use_the_header() {
+        let csv = OutputFormat::Csv
+            .parse("name,size\na,1\n\"b, c\",2\n")
+            .unwrap();
+        assert_eq!(
+            csv,
+            json!({ "rows": [{ "name": "a", "size": "1" }, { "name": "b, c", "size": "2" }] })
+        );
+
+        let tsv = OutputFormat::Tsv.parse("name\tsize\na\t1\n").unwrap();
+        assert_eq!(tsv, json!({ "rows": [{ "name": "a", "size": "1" }] }));
+    }
+
+    #[test]
+    fn key_value_splits_on_the_first_separator() {
+        let parsed = OutputFormat::KeyValue
+            .parse("NAME=\"Debian\"\nurl: http://x=y\n\n")
+            .unwrap();
+        assert_eq!(parsed, json!({ "NAME": "\"Debian\"", "url": "http://x=y" }));
+        assert!(OutputFormat::KeyValue.parse("no separator").is_err());
+    }
+
+    #[test]
+    fn regex_captures_named_groups_of_every_match() {
+        let format = OutputFormat::Regex(r"(?P<key>\w+)=(?P<value>\d+)".into());
+        let parsed = format.parse("a=1 b=2 c=x").unwrap();
+        assert_eq!(
+            parsed,
+            json!({ "matches": [{ "key": "a", "value": "1" }, { "key": "b", "value": "2" }] })
+        );
+    }
+
+    #[test]
+    fn regex_needs_named_groups() {
+        assert!(OutputFormat::Regex(r"(\d+)".into()).validate().is_err());
+        assert!(OutputFormat::Regex(r"(?P<n>\d+)".into()).validate().is_ok());
+        assert!(OutputFormat::Regex("(".into()).validate().is_err());
+    }
+}
@@ -0,0 +1,112 @@
+use anyhow::{Context, Result};
+use schemars::JsonSchema;
+use serde::Deserialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A fresh temporary working directory for every call of a tool
+#[derive(Debug, Deserialize, Clone, Default, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct ScratchConfig {
+    /// Directory whose contents are placed in the scratch directory before the
+    /// call, relative to the tool's `path`
+    #[serde(default)]
+    pub template: Option<String>,
+    /// How the template contents are placed in the scratch directory
+    #[serde(default)]
+    pub populate: PopulateMode,
+    /// Keep the directory of failed calls for debugging instead of removing it
+    #[serde(default)]
+    pub keep_on_failure: bool,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, Default, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum PopulateMode {
+    /// Copy the template recursively
+    #[default]
+    Copy,
+    /// Link each top-level entry of the template
+    Symlink,
+}
+
+/// Create a fresh temporary working directory for a tool call
+pub fn create_scratch_dir() -> Result<tempfile::TempDir> {
+    tempfile::Builder::new()
+        .prefix("mycommandmcp-")
+        .tempdir()
+        .context("Failed to create temporary working directory")
+}
+
+impl ScratchConfig {
+    /// The template directory, resolved against the tool's path
+    pub fn template_path(&self, tool_path: &str) -> Option<PathBuf> {
+        self.template
+            .as_ref()
+            .map(|template| Path::new(tool_path).join(template))
+    }
+
+    /// Place the template contents in the scratch directory `dir`
+    pub fn populate(&self, dir: &Path, tool_path: &str) -> Result<()> {
+        let Some(template) = self.template_path(tool_path) else {
+            return Ok(());
+        };
+        match self.populate {
+            PopulateMode::Copy => copy_dir(&template, dir),
+            PopulateMode::Symlink => link_entries(&template, dir),
+        }
+        .context(format!(
+            "Failed to populate working directory from template '{}'",
+            template.display()
+        ))
+    }
+}
+
+fn copy_dir(from: &Path, to: &Path) -> Result<()> {
+    for entry in fs::read_dir(from).context(format!("Failed to read '{}'", from.display()))? {
+        let entry = entry?;
+        let source = entry.path();
+        let target = to.join(entry.file_name());
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            fs::create_dir(&target)?;
+            copy_dir(&source, &target)?;
+        } else if file_type.is_symlink() {
+            symlink(&fs::read_link(&source)?, &target)?;
+        } else {
+            fs::copy(&source, &target).context(format!("Failed to copy '{}'", source.display()))?;
+        }
+    }
+    Ok(())
+}
+
+fn link_entries(from: &Path, to: &Path) -> Result<()> {
+    // Absolute targets keep the links valid whatever the working directory
+    let from = fs::canonicalize(from).context(format!("Failed to read '{}'", from.display()))?;
+    for entry in fs::read_dir(&from)? {
+        let entry = entry?;
+        symlink(&entry.path(), &to.join(entry.file_name()))?;
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn symlink(original: &Path, link: &Path) -> Result<()> {
+    std::os::unix::fs::symlink(original, link)
+        .context(format!("Failed to link '{}'", link.display()))
+}
+
+#[cfg(windows)]
+fn symlink(original: &Path, link: &Path) -> Result<()> {
+    let result = if link
+        .parent()
+        .map(|parent| parent.join(original))
+        .unwrap_or_else(|| original.to_path_buf())
+        .is_dir()
+    {
+        std::os::windows::fs::symlink_dir(original, link)
+    } else {
+        std::os::windows::fs::symlink_file(original, link)
+    };
+    result.context(format!("Failed to link '{}'", link.display()))
+}
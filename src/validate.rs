@@ -0,0 +1,318 @@
+use serde::Serialize;
+use std::env;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use crate::cli_parser::{is_url, load_config, ConfigData};
+
+/// Commands that can destroy data or take over the machine when they accept arbitrary arguments
+const RISKY_COMMANDS: &[&str] = &[
+    "rm", "rmdir", "dd", "mkfs", "shred", "chmod", "chown", "mv", "kill", "killall", "pkill",
+    "shutdown", "reboot", "sudo", "su", "sh", "bash", "zsh", "fish", "python", "python3", "perl",
+    "ruby", "node", "eval", "xargs", "find",
+];
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+    /// Noted in the report without counting as a problem
+    Info,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Finding {
+    pub severity: Severity,
+    /// The tool, prompt or resource the finding is about, e.g. `tool:list_files`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub subject: Option<String>,
+    /// The config file that defined the subject
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    pub message: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ValidationReport {
+    pub config: String,
+    pub errors: usize,
+    pub warnings: usize,
+    pub findings: Vec<Finding>,
+}
+
+impl ValidationReport {
+    fn new(config: &str) -> Self {
+        ValidationReport {
+            config: config.to_string(),
+            errors: 0,
+            warnings: 0,
+            findings: Vec::new(),
+        }
+    }
+
+    fn add(
+        &mut self,
+        severity: Severity,
+        subject: Option<String>,
+        source: Option<&str>,
+        message: String,
+    ) {
+        match severity {
+            Severity::Error => self.errors += 1,
+            Severity::Warning => self.warnings += 1,
+            Severity::Info => {}
+        }
+        self.findings.push(Finding {
+            severity,
+            subject,
+            source: source.map(|s| s.to_string()),
+            message,
+        });
+    }
+
+    /// Whether the report should make the validation fail
+    pub fn failed(&self, strict: bool) -> bool {
+        self.errors > 0 || (strict && self.warnings > 0)
+    }
+
+    /// Print the report in human-readable form
+    pub fn print(&self) {
+        println!("Validating {}", self.config);
+        for finding in &self.findings {
+            let severity = match finding.severity {
+                Severity::Error => "error",
+                Severity::Warning => "warning",
+                Severity::Info => "info",
+            };
+            let subject = finding
+                .subject
+                .as_ref()
+                .map(|subject| format!(" [{subject}]"))
+                .unwrap_or_default();
+            let source = finding
+                .source
+                .as_ref()
+                .map(|source| format!(" ({source})"))
+                .unwrap_or_default();
+            println!("{severity}{subject}{source}: {}", finding.message);
+        }
+        println!("{} error(s), {} warning(s)", self.errors, self.warnings);
+    }
+}
+
+/// Find an executable by name in the directories of `PATH`, or check an explicit path
+pub fn find_in_path(command: &str) -> Option<PathBuf> {
+    if command.contains('/') || command.contains('\\') {
+        let path = PathBuf::from(command);
+        return is_executable(&path).then_some(path);
+    }
+
+    let extensions: Vec<String> = if cfg!(target_os = "windows") {
+        env::var("PATHEXT")
+            .unwrap_or_else(|_| ".EXE;.CMD;.BAT;.COM".to_string())
+            .split(';')
+            .map(|ext| ext.to_string())
+            .chain(std::iter::once(String::new()))
+            .collect()
+    } else {
+        vec![String::new()]
+    };
+
+    env::split_paths(&env::var_os("PATH")?).find_map(|dir| {
+        extensions.iter().find_map(|ext| {
+            let candidate = dir.join(format!("{command}{ext}"));
+            is_executable(&candidate).then_some(candidate)
+        })
+    })
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    path.metadata()
+        .map(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+/// Load the configuration and lint it without starting the server
+pub async fn validate_config(config_path: &str, profile: Option<&str>) -> ValidationReport {
+    let mut report = ValidationReport::new(config_path);
+
+    match load_config(config_path, profile).await {
+        Ok(config) => check_config(&config, &mut report),
+        Err(e) => report.add(Severity::Error, None, Some(config_path), format!("{e:#}")),
+    }
+
+    report
+}
+
+fn check_config(config: &ConfigData, report: &mut ValidationReport) {
+    for warning in &config.warnings {
+        report.add(Severity::Warning, None, None, warning.clone());
+    }
+    for skipped in &config.skipped {
+        report.add(Severity::Info, None, None, format!("Skipped {skipped}"));
+    }
+
+    let mut tools: Vec<_> = config.tools.values().collect();
+    tools.sort_by(|a, b| a.name.cmp(&b.name));
+    for tool in tools {
+        let subject = Some(format!("tool:{}", tool.name));
+        let source = Some(tool.origin.source.as_str());
+
+        for program in tool.programs() {
+            // Relative paths such as `./build.sh` run from the tool's path
+            let is_relative_path = (program.contains('/') || program.contains('\\'))
+                && Path::new(program).is_relative();
+            if is_relative_path {
+                if find_in_path(&Path::new(&tool.path).join(program).to_string_lossy()).is_none() {
+                    report.add(
+                        Severity::Error,
+                        subject.clone(),
+                        source,
+                        format!(
+                            "Command '{}' is not an executable file in '{}'",
+                            program, tool.path
+                        ),
+                    );
+                }
+            } else if find_in_path(program).is_none() {
+                report.add(
+                    Severity::Error,
+                    subject.clone(),
+                    source,
+                    format!("Command '{}' was not found on PATH", program),
+                );
+            }
+        }
+
+        let path = Path::new(&tool.path);
+        if !path.exists() {
+            report.add(
+                Severity::Error,
+                subject.clone(),
+                source,
+                format!("Working directory '{}' does not exist", tool.path),
+            );
+        } else if !path.is_dir() {
+            report.add(
+                Severity::Error,
+                subject.clone(),
+                source,
+                format!("Working directory '{}' is not a directory", tool.path),
+            );
+        }
+
+        if let Some(template) = tool
+            .scratch
+            .as_ref()
+            .and_then(|scratch| scratch.template_path(&tool.path))
+        {
+            if !template.is_dir() {
+                report.add(
+                    Severity::Error,
+                    subject.clone(),
+                    source,
+                    format!(
+                        "Scratch template '{}' is not a directory",
+                        template.display()
+                    ),
+                );
+            }
+        }
+
+        if let Some(content_type) = &tool.content_type {
+            if mime_guess::Mime::from_str(content_type).is_err() {
+                report.add(
+                    Severity::Error,
+                    subject.clone(),
+                    source,
+                    format!("Invalid content_type '{}'", content_type),
+                );
+            }
+        }
+
+        // Arguments of inline scripts are positional parameters, not code,
+        // pipelines only pass them to the steps that opt in, and workflows to
+        // the command steps whose args use `{{args}}`
+        let receives_args: Vec<&str> = if !tool.accepts_args || tool.script.is_some() {
+            Vec::new()
+        } else if !tool.workflow.is_empty() {
+            tool.workflow
+                .iter()
+                .filter(|step| {
+                    step.args
+                        .as_ref()
+                        .is_some_and(|args| args.contains("{{args}}"))
+                })
+                .filter_map(|step| step.command.as_deref())
+                .collect()
+        } else if !tool.pipeline.is_empty() {
+            tool.pipeline
+                .iter()
+                .filter(|step| step.accepts_args)
+                .map(|step| step.command.as_str())
+                .collect()
+        } else {
+            vec![tool.command.as_str()]
+        };
+        for program in receives_args {
+            let command_name = Path::new(program)
+                .file_name()
+                .and_then(|name| name.to_str())
+                .unwrap_or(program);
+            if RISKY_COMMANDS.contains(&command_name) {
+                report.add(
+                    Severity::Warning,
+                    subject.clone(),
+                    source,
+                    format!(
+                        "Command '{}' accepts arbitrary arguments (accepts_args: true)",
+                        program
+                    ),
+                );
+            }
+        }
+    }
+
+    let mut prompts: Vec<_> = config.prompts.values().collect();
+    prompts.sort_by(|a, b| a.name.cmp(&b.name));
+    for prompt in prompts {
+        let subject = Some(format!("prompt:{}", prompt.name));
+        let source = Some(prompt.origin.source.as_str());
+
+        let paths = prompt
+            .path
+            .iter()
+            .chain(prompt.messages.iter().filter_map(|m| m.path.as_ref()));
+        for path in paths {
+            if !Path::new(path).is_file() {
+                report.add(
+                    Severity::Error,
+                    subject.clone(),
+                    source,
+                    format!("Prompt file '{}' does not exist", path),
+                );
+            }
+        }
+    }
+
+    let mut resources: Vec<_> = config.resources.values().collect();
+    resources.sort_by(|a, b| a.name.cmp(&b.name));
+    for resource in resources {
+        if !is_url(&resource.path) && !Path::new(&resource.path).is_file() {
+            report.add(
+                Severity::Error,
+                Some(format!("resource:{}", resource.name)),
+                Some(resource.origin.source.as_str()),
+                format!("Resource file '{}' does not exist", resource.path),
+            );
+        }
+    }
+}
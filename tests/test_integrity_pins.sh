@@ -0,0 +1,91 @@
+#!/bin/bash
+
+# Test script for sha256 pins and Ed25519 signatures of external configs
+cd /www/MCP/mycommandmcp
+
+echo "Testing MyCommandMCP integrity pinning of external configs..."
+echo ""
+
+mkdir -p /tmp/test_integrity_pins
+
+cat > /tmp/test_integrity_pins/tools.yaml << 'YAML'
+tools:
+  - name: "pinned_date"
+    description: "Pinned tool: Get current time"
+    command: "date"
+    path: "/"
+    accepts_args: false
+    accept_input: false
+YAML
+
+GOOD_SHA=$(sha256sum /tmp/test_integrity_pins/tools.yaml | cut -d' ' -f1)
+BAD_SHA=0000000000000000000000000000000000000000000000000000000000000000
+
+# Signing key, a valid signature and a signature of other content
+openssl genpkey -algorithm ed25519 -out /tmp/test_integrity_pins/key.pem 2>/dev/null
+PUBLIC_KEY=$(openssl pkey -in /tmp/test_integrity_pins/key.pem -pubout -outform DER | tail -c 32 | base64)
+openssl pkeyutl -sign -inkey /tmp/test_integrity_pins/key.pem -rawin \
+  -in /tmp/test_integrity_pins/tools.yaml | base64 -w0 > /tmp/test_integrity_pins/tools.yaml.sig
+echo "tampered" > /tmp/test_integrity_pins/other.txt
+openssl pkeyutl -sign -inkey /tmp/test_integrity_pins/key.pem -rawin \
+  -in /tmp/test_integrity_pins/other.txt | base64 -w0 > /tmp/test_integrity_pins/bad.sig
+
+cat > /tmp/test_integrity_pins/good.yaml << YAML
+tools: []
+external_configs:
+  - source: "tools.yaml"
+    sha256: "$GOOD_SHA"
+    signature:
+      public_key: "$PUBLIC_KEY"
+YAML
+
+cat > /tmp/test_integrity_pins/sha_mismatch.yaml << YAML
+tools: []
+external_configs:
+  - source: "tools.yaml"
+    sha256: "$BAD_SHA"
+YAML
+
+cat > /tmp/test_integrity_pins/bad_signature.yaml << YAML
+tools: []
+external_configs:
+  - source: "tools.yaml"
+    signature:
+      public_key: "$PUBLIC_KEY"
+      source: "bad.sig"
+YAML
+
+# An unpinned include of the same file comes first, the pin must still apply
+cat > /tmp/test_integrity_pins/repeated.yaml << YAML
+tools: []
+external_configs:
+  - "tools.yaml"
+  - source: "tools.yaml"
+    sha256: "$BAD_SHA"
+YAML
+
+echo "1. Matching sha256 and signature (should exit 0):"
+./target/release/mycommandmcp validate -c /tmp/test_integrity_pins/good.yaml
+echo "Exit code: $?"
+
+echo ""
+echo "2. sha256 mismatch (should exit 1 with a SHA-256 mismatch error):"
+./target/release/mycommandmcp validate -c /tmp/test_integrity_pins/sha_mismatch.yaml
+echo "Exit code: $?"
+
+echo ""
+echo "3. Signature of other content (should exit 1 with a signature verification error):"
+./target/release/mycommandmcp validate -c /tmp/test_integrity_pins/bad_signature.yaml
+echo "Exit code: $?"
+
+echo ""
+echo "4. Pinned include repeated after an unpinned one (should exit 1 with a SHA-256 mismatch error):"
+./target/release/mycommandmcp validate -c /tmp/test_integrity_pins/repeated.yaml
+echo "Exit code: $?"
+
+# Clean up
+echo ""
+echo "Cleaning up test files..."
+rm -rf /tmp/test_integrity_pins
+
+echo "Integrity pinning test completed."
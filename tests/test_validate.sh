@@ -0,0 +1,63 @@
+#!/bin/bash
+
+# Test script for the validate subcommand
+cd /www/MCP/mycommandmcp
+
+echo "Testing MyCommandMCP validate subcommand..."
+echo ""
+
+mkdir -p /tmp/test_validate
+
+# Valid configuration
+cat > /tmp/test_validate/valid.yaml << 'YAML'
+tools:
+  - name: "list_files"
+    description: "Lists files"
+    command: "ls"
+    path: "/"
+    accepts_args: true
+    accept_input: false
+YAML
+
+# Configuration with problems
+cat > /tmp/test_validate/invalid.yaml << 'YAML'
+tools:
+  - name: "missing_command"
+    description: "Command that does not exist"
+    command: "this-command-does-not-exist"
+    path: "/does/not/exist"
+    accepts_args: false
+    accept_input: false
+    content_type: "not a mime type"
+
+  - name: "remove"
+    description: "Risky tool"
+    command: "rm"
+    path: "/tmp"
+    accepts_args: true
+    accept_input: false
+
+prompts:
+  - name: "missing_file"
+    description: "Prompt with a missing file"
+    path: "/tmp/test_validate/missing.md"
+YAML
+
+echo "1. Validating a valid configuration (should exit 0):"
+./target/release/mycommandmcp validate -c /tmp/test_validate/valid.yaml
+echo "Exit code: $?"
+
+echo ""
+echo "2. Validating an invalid configuration (should exit 1):"
+./target/release/mycommandmcp validate -c /tmp/test_validate/invalid.yaml
+echo "Exit code: $?"
+
+echo ""
+echo "3. JSON output:"
+./target/release/mycommandmcp validate -c /tmp/test_validate/invalid.yaml --json
+echo "Exit code: $?"
+
+# Clean up
+rm -rf /tmp/test_validate
+echo ""
+echo "Test completed."